@@ -0,0 +1,14 @@
+// passes the commit EB is built from to the code, for its engine_getClientVersionV1 entry
+fn main() {
+    let commit = std::process::Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .unwrap_or_default();
+
+    println!("cargo:rustc-env=EB_GIT_COMMIT={}", commit.trim());
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
@@ -0,0 +1,123 @@
+use crate::{config, jwt, NodeRouter};
+use axum::{
+    extract::Request,
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use serde_json::json;
+use std::sync::Arc;
+use types::read_jwt_secret;
+
+// how admin requests prove they're allowed to change the node set
+pub enum AdminAuth {
+    // a static bearer token read from --admin-token-file
+    Token(String),
+    // a jwt signed with the general jwt secret, made the same way the CL makes them for the engine api
+    Jwt(jsonwebtoken::DecodingKey),
+}
+
+impl AdminAuth {
+    // the token file wins over the jwt secret
+    // None if neither is set, the admin api then rejects every request
+    pub fn new(settings: &config::Settings) -> Result<Option<Self>, String> {
+        if let Some(token_file) = &settings.admin_token_file {
+            let token = std::fs::read_to_string(token_file)
+                .map_err(|e| format!("Error reading admin token file: {}", e))?
+                .trim()
+                .to_string();
+            if token.is_empty() {
+                return Err(format!("Admin token file {} is empty", token_file));
+            }
+            return Ok(Some(AdminAuth::Token(token)));
+        }
+
+        match &settings.jwt_secret {
+            Some(jwt_secret) => Ok(Some(AdminAuth::Jwt(
+                jsonwebtoken::DecodingKey::from_secret(&read_jwt_secret(jwt_secret)?),
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn is_authorized(&self, token: &str) -> bool {
+        match self {
+            AdminAuth::Token(expected) => constant_time_eq(expected.as_bytes(), token.as_bytes()),
+            AdminAuth::Jwt(key) => jwt::decode_token(token, key)
+                .is_ok_and(|claims| jwt::iat_drift(&claims).abs() <= jwt::JWT_IAT_LEEWAY),
+        }
+    }
+}
+
+// so the time taken doesn't give away how much of the token was right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+// middleware for the admin routes, rejects requests without a valid `Authorization: Bearer` header
+pub async fn require_auth(
+    Extension(router): Extension<Arc<NodeRouter>>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Response {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let authorized = token.is_some_and(|token| {
+        router
+            .admin_auth
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|admin_auth| admin_auth.is_authorized(token))
+    });
+    if !authorized {
+        tracing::warn!(
+            "Rejected unauthorized admin request to {}",
+            request.uri().path()
+        );
+        return Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::WWW_AUTHENTICATE, "Bearer")
+            .body(json!({ "error": "Unauthorized" }).to_string().into())
+            .unwrap();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_auth() {
+        let token = AdminAuth::Token("secret".to_string());
+        assert!(token.is_authorized("secret"));
+        assert!(!token.is_authorized("secre"));
+        assert!(!token.is_authorized("secret2"));
+
+        let secret = [7u8; 32];
+        let jwt = AdminAuth::Jwt(jsonwebtoken::DecodingKey::from_secret(&secret));
+        let encoding_key = jsonwebtoken::EncodingKey::from_secret(&secret);
+        assert!(jwt.is_authorized(&types::make_jwt(&encoding_key).unwrap()));
+
+        // stale tokens and tokens signed with another secret are rejected
+        let stale = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &types::Claims {
+                iat: chrono::Utc::now().timestamp() - 120,
+            },
+            &encoding_key,
+        )
+        .unwrap();
+        assert!(!jwt.is_authorized(&stale));
+        let other_key = jsonwebtoken::EncodingKey::from_secret(&[8u8; 32]);
+        assert!(!jwt.is_authorized(&types::make_jwt(&other_key).unwrap()));
+    }
+}
@@ -0,0 +1,66 @@
+use serde_json::Value;
+use types::*;
+
+// number of blobs the CL asked for
+pub fn requested_len(request: &RpcRequest) -> Option<usize> {
+    request.params.get(0)?.as_array().map(|hashes| hashes.len())
+}
+
+// how many blobs a node's response had, a null response has none
+pub fn found(response: Option<&[Value]>) -> usize {
+    response.map_or(0, |blobs| {
+        blobs.iter().filter(|blob| !blob.is_null()).count()
+    })
+}
+
+// merges the nodes' responses per versioned hash, taking the first blob (with its proofs) found for each
+pub fn merge_blobs(method: &EngineMethod, len: usize, responses: &[Option<Vec<Value>>]) -> Value {
+    let mut blobs = vec![Value::Null; len];
+    for response in responses.iter().flatten() {
+        for (slot, blob) in blobs.iter_mut().zip(response) {
+            if slot.is_null() {
+                *slot = blob.clone();
+            }
+        }
+    }
+
+    // V2 is all or nothing, a response with a missing blob is null
+    if *method == EngineMethod::engine_getBlobsV2 && blobs.iter().any(Value::is_null) {
+        return Value::Null;
+    }
+    Value::Array(blobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_blobs() {
+        let responses = vec![
+            Some(vec![json!({"blob": "0x01"}), Value::Null, Value::Null]),
+            None,
+            Some(vec![
+                json!({"blob": "0x02"}),
+                json!({"blob": "0x03"}),
+                Value::Null,
+            ]),
+        ];
+        assert_eq!(found(responses[0].as_deref()), 1);
+        assert_eq!(found(responses[1].as_deref()), 0);
+
+        assert_eq!(
+            merge_blobs(&EngineMethod::engine_getBlobsV1, 3, &responses),
+            json!([{"blob": "0x01"}, {"blob": "0x03"}, null])
+        );
+        assert_eq!(
+            merge_blobs(&EngineMethod::engine_getBlobsV2, 3, &responses),
+            Value::Null
+        );
+        assert_eq!(
+            merge_blobs(&EngineMethod::engine_getBlobsV2, 2, &responses),
+            json!([{"blob": "0x01"}, {"blob": "0x03"}])
+        );
+    }
+}
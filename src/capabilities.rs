@@ -0,0 +1,72 @@
+use crate::config::CapabilitiesMode;
+
+// the engine methods EB has logic for, sent when EB asks a node for its capabilities itself
+pub const ENGINE_METHODS: &[&str] = &[
+    "engine_newPayloadV1",
+    "engine_newPayloadV2",
+    "engine_newPayloadV3",
+    "engine_forkchoiceUpdatedV1",
+    "engine_forkchoiceUpdatedV2",
+    "engine_forkchoiceUpdatedV3",
+    "engine_getPayloadV1",
+    "engine_getPayloadV2",
+    "engine_getPayloadV3",
+    "engine_getPayloadBodiesByHashV1",
+    "engine_getPayloadBodiesByRangeV1",
+    "engine_getClientVersionV1",
+    "engine_getBlobsV1",
+    "engine_getBlobsV2",
+];
+
+// combines the methods each node said it supports, keeping the order they were listed in
+pub fn combine(node_capabilities: &[Vec<String>], mode: CapabilitiesMode) -> Vec<String> {
+    let mut combined: Vec<String> = Vec::new();
+    for method in node_capabilities.iter().flatten() {
+        if combined.contains(method) {
+            continue;
+        }
+
+        let keep = match mode {
+            CapabilitiesMode::Intersection => node_capabilities
+                .iter()
+                .all(|capabilities| capabilities.contains(method)),
+            CapabilitiesMode::Union => true,
+        };
+        if keep {
+            combined.push(method.clone());
+        }
+    }
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine_capabilities() {
+        let to_strings = |methods: &[&str]| methods.iter().map(|m| m.to_string()).collect();
+        let node_capabilities: Vec<Vec<String>> = vec![
+            to_strings(&[
+                "engine_newPayloadV3",
+                "engine_getBlobsV1",
+                "engine_getBlobsV2",
+            ]),
+            to_strings(&["engine_newPayloadV3", "engine_getBlobsV1"]),
+        ];
+
+        assert_eq!(
+            combine(&node_capabilities, CapabilitiesMode::Intersection),
+            vec!["engine_newPayloadV3", "engine_getBlobsV1"]
+        );
+        assert_eq!(
+            combine(&node_capabilities, CapabilitiesMode::Union),
+            vec![
+                "engine_newPayloadV3",
+                "engine_getBlobsV1",
+                "engine_getBlobsV2"
+            ]
+        );
+        assert!(combine(&[], CapabilitiesMode::Intersection).is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{capabilities, client_versions, config, persist, table};
+use futures::future::join_all;
+use std::sync::Arc;
+use types::{node::Node, *};
+
+const HEADERS: &[&str] = &["NODE", "JWT", "CHAIN", "CLIENT", "CAPABILITIES", "SYNC"];
+
+// what `executionbackup check` found out about one node
+struct NodeReport {
+    url: String,
+    jwt: String,
+    chain: String,
+    client: Option<ClientVersionV1>,
+    client_error: Option<String>,
+    capabilities: Option<Vec<String>>,
+    capabilities_error: Option<String>,
+    sync: String,
+    // the jwt was rejected, the node couldn't be reached or it's on another chain
+    failed: bool,
+}
+
+impl NodeReport {
+    fn row(&self) -> Vec<String> {
+        let client = match (&self.client, &self.client_error) {
+            (Some(version), _) => format!("{} {}", version.name, version.version),
+            (None, Some(e)) => format!("error: {}", e),
+            (None, None) => "-".to_string(),
+        };
+        let capabilities = match (&self.capabilities, &self.capabilities_error) {
+            (Some(methods), _) => format!("{} methods", methods.len()),
+            (None, Some(e)) => format!("error: {}", e),
+            (None, None) => "-".to_string(),
+        };
+        vec![
+            self.url.clone(),
+            self.jwt.clone(),
+            self.chain.clone(),
+            client,
+            capabilities,
+            self.sync.clone(),
+        ]
+    }
+}
+
+async fn check_node(node: Arc<Node>, fork_config: &ForkConfig) -> NodeReport {
+    let mut report = NodeReport {
+        url: node.url.clone(),
+        jwt: "-".to_string(),
+        chain: "-".to_string(),
+        client: None,
+        client_error: None,
+        capabilities: None,
+        capabilities_error: None,
+        sync: "-".to_string(),
+        failed: true,
+    };
+
+    // nothing else can be asked if the node is down or won't take our jwt
+    match node.check_status().await {
+        Err(e) => {
+            report.sync = format!("unreachable: {}", e);
+            return report;
+        }
+        Ok(health) if health.status == SyncingStatus::AuthFailed => {
+            report.jwt = "rejected".to_string();
+            return report;
+        }
+        Ok(health) => {
+            report.jwt = "ok".to_string();
+            report.sync = match (health.status, health.head) {
+                (SyncingStatus::Synced, Some(head)) => format!("synced at {}", head.number),
+                (SyncingStatus::Synced, None) => "synced".to_string(),
+                _ => "syncing".to_string(),
+            };
+        }
+    }
+
+    report.chain = match node
+        .verify_chain(fork_config.chain_id, fork_config.genesis_hash)
+        .await
+    {
+        Ok(()) => {
+            report.failed = false;
+            format!("ok ({})", fork_config.chain_id)
+        }
+        Err(e) => e.to_string(),
+    };
+
+    match node
+        .get_client_version(&client_versions::own_client_version())
+        .await
+    {
+        Ok(version) => report.client = version,
+        Err(e) => report.client_error = Some(e.to_string()),
+    }
+    match node
+        .exchange_capabilities(capabilities::ENGINE_METHODS)
+        .await
+    {
+        Ok(methods) => report.capabilities = methods,
+        Err(e) => report.capabilities_error = Some(e.to_string()),
+    }
+
+    report
+}
+
+// warnings that don't fail the check, but are worth fixing before the CL is pointed at EB
+fn warnings(reports: &[NodeReport]) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    let versions: Vec<(String, ClientVersionV1)> = reports
+        .iter()
+        .filter_map(|report| Some((report.url.clone(), report.client.clone()?)))
+        .collect();
+    warnings.extend(client_versions::mismatches(&versions));
+
+    let node_capabilities: Vec<Vec<String>> = reports
+        .iter()
+        .filter_map(|report| report.capabilities.clone())
+        .collect();
+    let union = capabilities::combine(&node_capabilities, config::CapabilitiesMode::Union);
+    let intersection =
+        capabilities::combine(&node_capabilities, config::CapabilitiesMode::Intersection);
+    for method in union.iter().filter(|method| !intersection.contains(method)) {
+        let missing: Vec<&str> = reports
+            .iter()
+            .filter(|report| {
+                report
+                    .capabilities
+                    .as_ref()
+                    .is_some_and(|methods| !methods.contains(method))
+            })
+            .map(|report| report.url.as_str())
+            .collect();
+        warnings.push(format!(
+            "{} is not supported by {}",
+            method,
+            missing.join(", ")
+        ));
+    }
+
+    if !reports
+        .iter()
+        .any(|report| report.sync.starts_with("synced"))
+    {
+        warnings.push("No node is synced".to_string());
+    }
+
+    warnings
+}
+
+// connects to every node the server would use and prints what it found
+// returns false if any node failed, so deploy scripts can stop before EB is started
+pub async fn run(matches: &clap::ArgMatches<'_>) -> bool {
+    match check(matches).await {
+        Ok(passed) => passed,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+async fn check(matches: &clap::ArgMatches<'_>) -> Result<bool, String> {
+    let settings = config::Settings::resolve(matches)?;
+
+    let general_jwt = match &settings.jwt_secret {
+        Some(general_jwt_path) => Some(
+            read_jwt(general_jwt_path).map_err(|e| format!("Error reading general jwt: {}", e))?,
+        ),
+        None => None,
+    };
+
+    let mut configs = settings.nodes;
+    if let Some(state_file) = &settings.state_file {
+        configs = persist::NodeStore::load(state_file.into())?
+            .nodes()
+            .await
+            .merge(configs)?;
+    }
+    let nodes = create_nodes(configs, general_jwt.as_ref(), settings.request_timeout)?;
+
+    let fork_config = &settings.fork_config;
+    let reports = join_all(nodes.into_iter().map(|node| check_node(node, fork_config))).await;
+
+    let rows: Vec<Vec<String>> = reports.iter().map(NodeReport::row).collect();
+    println!("{}", table::render(HEADERS, &rows));
+
+    let warnings = warnings(&reports);
+    if !warnings.is_empty() {
+        println!();
+        for warning in warnings.iter() {
+            println!("warning: {}", warning);
+        }
+    }
+
+    let failed = reports.iter().filter(|report| report.failed).count();
+    println!();
+    println!(
+        "{} of {} nodes passed",
+        reports.len() - failed,
+        reports.len()
+    );
+    Ok(failed == 0)
+}
@@ -0,0 +1,179 @@
+use crate::{NodeRouter, VERSION};
+use futures::future::join_all;
+use std::sync::Arc;
+use tokio::time::Duration;
+use types::ClientVersionV1;
+
+// set by build.rs, empty if EB wasn't built from a git checkout
+const GIT_COMMIT: &str = env!("EB_GIT_COMMIT");
+
+// how often every node is asked which client version it's running
+const POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// EB's own entry in engine_getClientVersionV1 responses
+pub fn own_client_version() -> ClientVersionV1 {
+    ClientVersionV1 {
+        code: "EB".to_string(),
+        name: "executionbackup".to_string(),
+        version: VERSION.to_string(),
+        commit: format!("0x{:0<8}", GIT_COMMIT.get(..8).unwrap_or_default()),
+    }
+}
+
+// every node's client versions in one list without duplicates, followed by EB's own
+pub fn client_versions(resps: Vec<Vec<ClientVersionV1>>) -> Vec<ClientVersionV1> {
+    let mut versions: Vec<ClientVersionV1> = Vec::new();
+    for version in resps.into_iter().flatten() {
+        if !versions.contains(&version) {
+            versions.push(version);
+        }
+    }
+    versions.push(own_client_version());
+    versions
+}
+
+// warnings for nodes running the same client on different versions
+pub fn mismatches(versions: &[(String, ClientVersionV1)]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let mut seen_codes: Vec<&str> = Vec::new();
+    for (_, version) in versions.iter() {
+        if seen_codes.contains(&version.code.as_str()) {
+            continue;
+        }
+        seen_codes.push(&version.code);
+
+        let same_client: Vec<&(String, ClientVersionV1)> = versions
+            .iter()
+            .filter(|(_, other)| other.code == version.code)
+            .collect();
+        if same_client
+            .iter()
+            .all(|(_, other)| other.version == version.version)
+        {
+            continue;
+        }
+
+        warnings.push(format!(
+            "Nodes running {} are on different versions: {}",
+            version.name,
+            same_client
+                .iter()
+                .map(|(url, other)| format!("{} on {}", url, other.version))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+    }
+    warnings
+}
+
+// every node's last known client version, by node url
+pub async fn node_versions(router: &NodeRouter) -> Vec<(String, ClientVersionV1)> {
+    let nodes = router.nodes.lock().await.clone();
+    let mut versions = Vec::with_capacity(nodes.len());
+    for node in nodes.iter() {
+        if let Some(version) = node.client_version.read().await.clone() {
+            versions.push((node.url.clone(), version));
+        }
+    }
+    versions
+}
+
+// asks every node for its client version and warns about nodes of the same client on different versions
+async fn update(router: &NodeRouter) {
+    let nodes = router.nodes.lock().await.clone();
+    let own_version = own_client_version();
+    let results = join_all(
+        nodes
+            .iter()
+            .map(|node| node.get_client_version(&own_version)),
+    )
+    .await;
+
+    for (node, result) in nodes.iter().zip(results) {
+        match result {
+            Ok(Some(version)) => tracing::debug!(
+                "{} is running {} {} ({})",
+                node.url,
+                version.name,
+                version.version,
+                version.commit
+            ),
+            Ok(None) => tracing::debug!("{} didn't say which client it's running", node.url),
+            Err(e) => tracing::debug!("Could not get client version of {}: {}", node.url, e),
+        }
+    }
+
+    for warning in mismatches(&node_versions(router).await) {
+        tracing::warn!("{}", warning);
+    }
+}
+
+pub async fn poll_client_versions(router: Arc<NodeRouter>) {
+    loop {
+        update(&router).await;
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_versions() {
+        let geth = ClientVersionV1 {
+            code: "GE".to_string(),
+            name: "Geth".to_string(),
+            version: "1.14.0".to_string(),
+            commit: "0x12345678".to_string(),
+        };
+        let nethermind = ClientVersionV1 {
+            code: "NM".to_string(),
+            name: "Nethermind".to_string(),
+            version: "1.27.0".to_string(),
+            commit: "0x9abcdef0".to_string(),
+        };
+
+        // two geth nodes on the same version show up once
+        let versions = client_versions(vec![
+            vec![geth.clone()],
+            vec![nethermind.clone()],
+            vec![geth.clone()],
+        ]);
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions[0], geth);
+        assert_eq!(versions[1], nethermind);
+        assert_eq!(versions[2].code, "EB");
+        assert_eq!(versions[2].commit.len(), 10);
+    }
+
+    #[test]
+    fn test_mismatches() {
+        let nethermind = |version: &str| ClientVersionV1 {
+            code: "NM".to_string(),
+            name: "Nethermind".to_string(),
+            version: version.to_string(),
+            commit: "0x9abcdef0".to_string(),
+        };
+        let geth = ClientVersionV1 {
+            code: "GE".to_string(),
+            name: "Geth".to_string(),
+            version: "1.14.0".to_string(),
+            commit: "0x12345678".to_string(),
+        };
+
+        let versions = vec![
+            ("http://a:8551".to_string(), nethermind("1.27.0")),
+            ("http://b:8551".to_string(), geth),
+            ("http://c:8551".to_string(), nethermind("1.27.0")),
+        ];
+        assert!(mismatches(&versions).is_empty());
+
+        let mut versions = versions;
+        versions.push(("http://d:8551".to_string(), nethermind("1.26.0")));
+        assert_eq!(
+            mismatches(&versions),
+            vec!["Nodes running Nethermind are on different versions: http://a:8551 on 1.27.0, http://c:8551 on 1.27.0, http://d:8551 on 1.26.0"]
+        );
+    }
+}
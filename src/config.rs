@@ -0,0 +1,441 @@
+use ethereum_types::H256;
+use serde::Deserialize;
+use std::{collections::HashMap, fmt::Display, net::SocketAddr, path::Path, str::FromStr};
+use tokio::time::Duration;
+use types::{circuit::CircuitBreakerConfig, EngineMethod, ForkConfig, NodeConfig};
+
+// settings read from --config, keys are named like the flags they stand in for
+// every setting is optional, flags given on the command line win over the file
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ConfigFile {
+    pub jwt_secret: Option<String>,
+    pub server: ServerConfig,
+    pub network: NetworkConfig,
+    pub consensus: ConsensusConfig,
+    pub timeouts: TimeoutConfig,
+    pub methods: MethodsConfig,
+    pub nodes: Vec<NodeConfig>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ServerConfig {
+    pub listen_addr: Option<String>,
+    pub port: Option<u16>,
+    pub log_level: Option<String>,
+    pub node_timings: Option<bool>,
+    pub state_file: Option<String>,
+    pub admin_addr: Option<String>,
+    pub admin_token_file: Option<String>,
+}
+
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Network {
+    #[default]
+    Mainnet,
+    Holesky,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct NetworkConfig {
+    pub name: Option<Network>,
+    // override the network's fork epochs, for devnets and shadow forks
+    pub shanghai_fork_epoch: Option<u64>,
+    pub cancun_fork_epoch: Option<u64>,
+    // override the chain nodes have to be on
+    pub chain_id: Option<u64>,
+    pub genesis_hash: Option<H256>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ConsensusConfig {
+    pub fcu_majority: Option<f32>,
+    pub max_head_lag: Option<u64>,
+    pub circuit_failure_threshold: Option<u32>,
+    pub circuit_open_time: Option<u64>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TimeoutConfig {
+    pub request_timeout: Option<u64>,
+    pub recheck_interval: Option<u64>,
+}
+
+// how a request for an engine method EB has no logic of its own for is routed
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MethodStrategy {
+    // only the primary node gets the request
+    #[default]
+    Primary,
+    // the primary node answers, the other alive nodes get the request in the background
+    BroadcastAndPrimary,
+}
+
+impl FromStr for MethodStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "primary" => Ok(MethodStrategy::Primary),
+            "broadcast-and-primary" => Ok(MethodStrategy::BroadcastAndPrimary),
+            _ => Err(format!(
+                "unknown strategy {}, expected primary or broadcast-and-primary",
+                s
+            )),
+        }
+    }
+}
+
+// how the nodes' engine_exchangeCapabilities responses are combined for the CL
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CapabilitiesMode {
+    // methods every node supports
+    #[default]
+    Intersection,
+    // methods any node supports, they're only sent to the nodes that support them
+    Union,
+}
+
+impl FromStr for CapabilitiesMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "intersection" => Ok(CapabilitiesMode::Intersection),
+            "union" => Ok(CapabilitiesMode::Union),
+            _ => Err(format!(
+                "unknown capabilities mode {}, expected intersection or union",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct MethodsConfig {
+    pub unknown_method_strategy: Option<MethodStrategy>,
+    pub capabilities: Option<CapabilitiesMode>,
+    // strategies for single unknown methods by name, e.g. engine_getInclusionListV1 = "primary"
+    pub strategies: HashMap<String, MethodStrategy>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MethodStrategies {
+    pub default: MethodStrategy,
+    pub methods: HashMap<String, MethodStrategy>,
+}
+
+impl MethodStrategies {
+    pub fn get(&self, method: &str) -> MethodStrategy {
+        self.methods.get(method).copied().unwrap_or(self.default)
+    }
+}
+
+// strategies only change how unknown methods are routed, one for a method EB handles itself would be ignored
+fn check_method_strategies(strategies: &HashMap<String, MethodStrategy>) -> Result<(), String> {
+    for method in strategies.keys() {
+        if let Ok(known) = serde_json::from_value::<EngineMethod>(serde_json::json!(method)) {
+            if !matches!(known, EngineMethod::Unknown(_)) {
+                return Err(format!(
+                    "{} is handled by EB itself, method strategies only apply to engine methods it doesn't know",
+                    method
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+impl ConfigFile {
+    // yaml if the file ends in .yaml or .yml, toml otherwise
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config file {}: {}", path.display(), e))?;
+
+        let config = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml") | Some("yml") => {
+                serde_yaml::from_str(&contents).map_err(|e| e.to_string())
+            }
+            _ => toml::from_str(&contents).map_err(|e| e.to_string()),
+        };
+        config.map_err(|e| format!("Could not parse config file {}: {}", path.display(), e))
+    }
+}
+
+impl NetworkConfig {
+    pub fn fork_config(&self, network: Network) -> ForkConfig {
+        let mut fork_config = match network {
+            Network::Mainnet => ForkConfig::mainnet(),
+            Network::Holesky => ForkConfig::holesky(),
+        };
+
+        if let Some(shanghai_fork_epoch) = self.shanghai_fork_epoch {
+            fork_config.shanghai_fork_epoch = Some(shanghai_fork_epoch);
+        }
+        if let Some(cancun_fork_epoch) = self.cancun_fork_epoch {
+            fork_config.cancun_fork_epoch = Some(cancun_fork_epoch);
+        }
+        if let Some(chain_id) = self.chain_id {
+            fork_config.chain_id = chain_id;
+        }
+        if let Some(genesis_hash) = self.genesis_hash {
+            fork_config.genesis_hash = genesis_hash;
+        }
+        fork_config
+    }
+}
+
+// everything EB runs with, after the command line and config file are combined
+pub struct Settings {
+    pub listen_addr: String,
+    pub port: u16,
+    pub log_level: String,
+    pub node_timings_enabled: bool,
+    pub state_file: Option<String>,
+    // the admin api is only served on its own listener, it's disabled if this isn't set
+    pub admin_addr: Option<SocketAddr>,
+    pub admin_token_file: Option<String>,
+    pub jwt_secret: Option<String>,
+    pub network: Network,
+    pub fork_config: ForkConfig,
+    pub fcu_majority: f32,
+    pub max_head_lag: u64,
+    pub circuit_config: CircuitBreakerConfig,
+    pub request_timeout: Duration,
+    pub recheck_interval: Duration,
+    // for engine methods EB doesn't know
+    pub method_strategies: MethodStrategies,
+    pub capabilities_mode: CapabilitiesMode,
+    pub nodes: Vec<NodeConfig>,
+}
+
+impl Settings {
+    pub fn resolve(matches: &clap::ArgMatches) -> Result<Self, String> {
+        let file = match matches.value_of("config") {
+            Some(path) => ConfigFile::load(Path::new(path))?,
+            None => ConfigFile::default(),
+        };
+
+        let fcu_majority = setting(matches, "fcu-majority", file.consensus.fcu_majority)?;
+        if !(0.0..=1.0).contains(&fcu_majority) {
+            return Err("fcu majority must be between 0.0 and 1.0".to_string());
+        }
+
+        let network = match matches.is_present("holesky") {
+            true => Network::Holesky,
+            false => file.network.name.unwrap_or_default(),
+        };
+
+        // --nodes replaces the config file's nodes instead of adding to them
+        let nodes = match matches.value_of("nodes") {
+            Some(nodes) => nodes
+                .split(',')
+                .map(NodeConfig::from_spec)
+                .collect::<Result<Vec<NodeConfig>, String>>()?,
+            None => file.nodes,
+        };
+        if nodes.is_empty() {
+            return Err("No nodes given, use --nodes or add nodes to the config file".to_string());
+        }
+
+        check_method_strategies(&file.methods.strategies)?;
+
+        Ok(Settings {
+            listen_addr: setting(matches, "listen-addr", file.server.listen_addr)?,
+            port: setting(matches, "port", file.server.port)?,
+            log_level: setting(matches, "log-level", file.server.log_level)?,
+            node_timings_enabled: matches.is_present("node-timings")
+                || file.server.node_timings.unwrap_or(false),
+            state_file: optional_setting(matches, "state-file", file.server.state_file),
+            admin_addr: optional_setting(matches, "admin-addr", file.server.admin_addr)
+                .map(|admin_addr| {
+                    admin_addr
+                        .parse()
+                        .map_err(|e| format!("Invalid value for --admin-addr: {}", e))
+                })
+                .transpose()?,
+            admin_token_file: optional_setting(
+                matches,
+                "admin-token-file",
+                file.server.admin_token_file,
+            ),
+            jwt_secret: optional_setting(matches, "jwt-secret", file.jwt_secret),
+            network,
+            fork_config: file.network.fork_config(network),
+            fcu_majority,
+            max_head_lag: setting(matches, "max-head-lag", file.consensus.max_head_lag)?,
+            circuit_config: CircuitBreakerConfig {
+                failure_threshold: nonzero_setting(
+                    matches,
+                    "circuit-failure-threshold",
+                    file.consensus.circuit_failure_threshold,
+                )?,
+                open_duration: Duration::from_secs(setting(
+                    matches,
+                    "circuit-open-time",
+                    file.consensus.circuit_open_time,
+                )?),
+            },
+            request_timeout: Duration::from_millis(nonzero_setting(
+                matches,
+                "request-timeout",
+                file.timeouts.request_timeout,
+            )?),
+            recheck_interval: Duration::from_secs(nonzero_setting(
+                matches,
+                "recheck-interval",
+                file.timeouts.recheck_interval,
+            )?),
+            method_strategies: MethodStrategies {
+                methods: file.methods.strategies,
+                default: setting(
+                    matches,
+                    "unknown-method-strategy",
+                    file.methods.unknown_method_strategy,
+                )?,
+            },
+            capabilities_mode: setting(matches, "capabilities", file.methods.capabilities)?,
+            nodes,
+        })
+    }
+}
+
+// the flag's value if it was given on the command line, then the config file's, then the flag's default
+fn setting<T>(matches: &clap::ArgMatches, name: &str, file_value: Option<T>) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if matches.occurrences_of(name) == 0 {
+        if let Some(file_value) = file_value {
+            return Ok(file_value);
+        }
+    }
+
+    let value = matches
+        .value_of(name)
+        .ok_or_else(|| format!("--{} is required", name))?;
+    value
+        .parse()
+        .map_err(|e| format!("Invalid value for --{}: {}", name, e))
+}
+
+// for counts and durations where 0 would trip the circuit at once, fail every request or busy-loop
+fn nonzero_setting<T>(matches: &clap::ArgMatches, name: &str, file_value: Option<T>) -> Result<T, String>
+where
+    T: FromStr + Default + PartialEq,
+    T::Err: Display,
+{
+    let value = setting(matches, name, file_value)?;
+    if value == T::default() {
+        return Err(format!("--{} must be greater than 0", name));
+    }
+    Ok(value)
+}
+
+// for flags without a default
+fn optional_setting(
+    matches: &clap::ArgMatches,
+    name: &str,
+    file_value: Option<String>,
+) -> Option<String> {
+    matches.value_of(name).map(str::to_string).or(file_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::NodeRole;
+
+    #[test]
+    fn test_parse_config_file() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            jwt-secret = "/secrets/jwt.hex"
+
+            [server]
+            port = 8000
+
+            [network]
+            name = "holesky"
+
+            [consensus]
+            fcu-majority = 0.5
+
+            [methods]
+            unknown-method-strategy = "broadcast-and-primary"
+            strategies = { engine_getInclusionListV1 = "primary" }
+
+            [[nodes]]
+            url = "http://geth:8551"
+            label = "geth"
+            role = "primary"
+            weight = 2
+            headers = { "X-Api-Key" = "secret" }
+
+            [[nodes]]
+            url = "http://nethermind:8551"
+            timeout = 2000
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.server.port, Some(8000));
+        assert_eq!(config.network.name, Some(Network::Holesky));
+        assert_eq!(config.consensus.fcu_majority, Some(0.5));
+        assert_eq!(
+            config.methods.unknown_method_strategy,
+            Some(MethodStrategy::BroadcastAndPrimary)
+        );
+        assert_eq!(
+            config.methods.strategies["engine_getInclusionListV1"],
+            MethodStrategy::Primary
+        );
+        assert!(check_method_strategies(&config.methods.strategies).is_ok());
+        // getBlobs is aggregated by EB, a strategy for it would never be used
+        let known = HashMap::from([("engine_getBlobsV1".to_string(), MethodStrategy::Primary)]);
+        assert!(check_method_strategies(&known).is_err());
+        assert_eq!(config.nodes.len(), 2);
+        assert_eq!(config.nodes[0].role, NodeRole::Primary);
+        assert_eq!(config.nodes[0].headers["X-Api-Key"], "secret");
+        assert_eq!(config.nodes[1].weight, 1);
+        assert_eq!(config.nodes[1].timeout, Some(2000));
+
+        let yaml_config: ConfigFile = serde_yaml::from_str(
+            "
+            jwt-secret: /secrets/jwt.hex
+            nodes:
+              - url: http://geth:8551
+                role: backup
+            ",
+        )
+        .unwrap();
+        assert_eq!(yaml_config.nodes[0].role, NodeRole::Backup);
+
+        assert!(toml::from_str::<ConfigFile>("[server]\nprot = 8000").is_err());
+    }
+
+    #[test]
+    fn test_nonzero_setting() {
+        let matches = clap::App::new("executionbackup")
+            .arg(
+                clap::Arg::with_name("recheck-interval")
+                    .long("recheck-interval")
+                    .takes_value(true)
+                    .default_value("15"),
+            )
+            .get_matches_from(vec!["executionbackup"]);
+
+        assert_eq!(nonzero_setting::<u64>(&matches, "recheck-interval", None), Ok(15));
+        assert_eq!(nonzero_setting(&matches, "recheck-interval", Some(5u64)), Ok(5));
+        assert!(nonzero_setting(&matches, "recheck-interval", Some(0u64)).is_err());
+    }
+}
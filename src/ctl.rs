@@ -0,0 +1,310 @@
+use crate::table;
+use serde_json::{json, Value};
+use types::{make_jwt, read_jwt};
+
+// the admin api is only served on --admin-addr, this is the address the docs suggest for it
+const DEFAULT_URL: &str = "http://127.0.0.1:7001";
+
+// how `executionbackup ctl` proves it's allowed to use the admin api, the same ways the server accepts
+enum Auth {
+    Token(String),
+    Jwt(jsonwebtoken::EncodingKey),
+    None,
+}
+
+// the flags every ctl command takes, they can be given before or after the command
+// clap only records them on the matches they were given with
+struct GlobalFlags<'a> {
+    ctl: &'a clap::ArgMatches<'a>,
+    command: &'a clap::ArgMatches<'a>,
+}
+
+impl<'a> GlobalFlags<'a> {
+    fn value_of(&self, name: &str) -> Option<&'a str> {
+        self.command
+            .value_of(name)
+            .or_else(|| self.ctl.value_of(name))
+    }
+
+    fn is_present(&self, name: &str) -> bool {
+        self.command.is_present(name) || self.ctl.is_present(name)
+    }
+}
+
+struct AdminClient {
+    client: reqwest::Client,
+    url: String,
+    auth: Auth,
+}
+
+impl AdminClient {
+    fn new(flags: &GlobalFlags) -> Result<Self, String> {
+        let auth = if let Some(token_file) = flags.value_of("admin-token-file") {
+            let token = std::fs::read_to_string(token_file)
+                .map_err(|e| format!("Error reading admin token file: {}", e))?;
+            Auth::Token(token.trim().to_string())
+        } else if let Some(jwt_secret) = flags.value_of("jwt-secret") {
+            Auth::Jwt(read_jwt(jwt_secret)?)
+        } else {
+            Auth::None
+        };
+
+        Ok(AdminClient {
+            client: reqwest::Client::new(),
+            url: flags
+                .value_of("url")
+                .unwrap_or(DEFAULT_URL)
+                .trim_end_matches('/')
+                .to_string(),
+            auth,
+        })
+    }
+
+    async fn get(&self, path: &str) -> Result<Value, String> {
+        self.send(self.client.get(format!("{}{}", self.url, path)))
+            .await
+    }
+
+    async fn post(&self, path: &str, body: Value) -> Result<Value, String> {
+        self.send(
+            self.client
+                .post(format!("{}{}", self.url, path))
+                .json(&body),
+        )
+        .await
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<Value, String> {
+        let request = match &self.auth {
+            Auth::Token(token) => request.bearer_auth(token),
+            // a fresh token for every request, the server rejects ones more than a minute old
+            Auth::Jwt(key) => request
+                .bearer_auth(make_jwt(key).map_err(|e| format!("Could not make jwt: {}", e))?),
+            Auth::None => request,
+        };
+
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| format!("Could not reach {}: {}", self.url, e))?;
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| format!("Could not read the response: {}", e))?;
+        let body: Value = serde_json::from_str(&body).unwrap_or(Value::String(body));
+
+        if !status.is_success() {
+            let error = match body.get("error").and_then(Value::as_str) {
+                Some(error) => error.to_string(),
+                None => body.to_string(),
+            };
+            return Err(format!("{} ({})", error, status));
+        }
+        Ok(body)
+    }
+}
+
+fn string(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+// block numbers are hex quantities in the reports
+fn head_number(number: &Value) -> String {
+    match number
+        .as_str()
+        .and_then(|number| number.strip_prefix("0x"))
+        .and_then(|number| u64::from_str_radix(number, 16).ok())
+    {
+        Some(number) => number.to_string(),
+        None => string(number),
+    }
+}
+
+// one row per node in a /metrics or /recheck report
+fn nodes_table(report: &Value) -> String {
+    let mut urls: Vec<&String> = match report["node_states"].as_object() {
+        Some(states) => states.keys().collect(),
+        None => Vec::new(),
+    };
+    urls.sort();
+
+    let primary = report["primary_node"].as_str();
+    let rows: Vec<Vec<String>> = urls
+        .into_iter()
+        .map(|url| {
+            let client = &report["client_versions"][url];
+            let client = match client.is_null() {
+                true => "-".to_string(),
+                false => format!("{} {}", string(&client["name"]), string(&client["version"])),
+            };
+            let response_time = match report["response_times"][url].as_f64() {
+                Some(micros) => format!("{:.2}ms", micros / 1000.0),
+                None => "-".to_string(),
+            };
+            vec![
+                format!("{}{}", url, if primary == Some(url) { " *" } else { "" }),
+                string(&report["labels"][url]),
+                string(&report["node_states"][url]),
+                head_number(&report["heads"][url]["number"]),
+                response_time,
+                string(&report["circuit_states"][url]),
+                string(&report["queue_depths"][url]),
+                client,
+            ]
+        })
+        .collect();
+
+    table::render(
+        &[
+            "NODE", "LABEL", "STATE", "HEAD", "RESPONSE", "CIRCUIT", "QUEUE", "CLIENT",
+        ],
+        &rows,
+    )
+}
+
+fn print_status(report: &Value) {
+    let count = |key: &str| report[key].as_array().map_or(0, Vec::len);
+    println!("Primary node: {}", string(&report["primary_node"]));
+    println!(
+        "Nodes: {} synced, {} syncing, {} lagging, {} dead",
+        count("alive_nodes"),
+        count("syncing_nodes"),
+        count("lagging_nodes"),
+        count("dead_nodes")
+    );
+    if let Some(warnings) = report["client_version_warnings"].as_array() {
+        for warning in warnings {
+            println!("warning: {}", string(warning));
+        }
+    }
+}
+
+fn disagreements_table(disagreements: &Value) -> String {
+    let empty = Vec::new();
+    let rows: Vec<Vec<String>> = disagreements
+        .as_array()
+        .unwrap_or(&empty)
+        .iter()
+        .map(|disagreement| {
+            let time = disagreement["timestamp"]
+                .as_i64()
+                .and_then(chrono::DateTime::from_timestamp_millis)
+                .map_or("-".to_string(), |time| {
+                    time.format("%Y-%m-%d %H:%M:%S").to_string()
+                });
+            let mut votes: Vec<String> = match disagreement["votes"].as_object() {
+                Some(votes) => votes
+                    .iter()
+                    .map(|(url, vote)| format!("{}={}", url, string(&vote["status"])))
+                    .collect(),
+                None => Vec::new(),
+            };
+            votes.sort();
+            vec![
+                time,
+                string(&disagreement["method"]),
+                string(&disagreement["block_hash"]),
+                string(&disagreement["outcome"]),
+                votes.join(", "),
+            ]
+        })
+        .collect();
+
+    table::render(&["TIME", "METHOD", "BLOCK", "ANSWERED", "VOTES"], &rows)
+}
+
+async fn ctl(matches: &clap::ArgMatches<'_>) -> Result<(), String> {
+    let (command, command_matches) = matches.subcommand();
+    let flags = GlobalFlags {
+        ctl: matches,
+        command: command_matches.unwrap_or(matches),
+    };
+    let client = AdminClient::new(&flags)?;
+    let json_output = flags.is_present("json");
+    let matches = flags.command;
+
+    let (resp, print): (Value, fn(&Value)) = match command {
+        "status" => (client.get("/metrics").await?, print_status),
+        "nodes" => (client.get("/metrics").await?, |report| {
+            println!("{}", nodes_table(report))
+        }),
+        "recheck" => (client.get("/recheck").await?, |report| {
+            println!("{}", nodes_table(report));
+            if let Some(micros) = report["recheck_time"].as_f64() {
+                println!();
+                println!("Rechecked in {:.2}ms", micros / 1000.0);
+            }
+        }),
+        "add" => {
+            // clap makes sure at least one node is given
+            let nodes: Vec<&str> = matches.values_of("nodes").unwrap().collect();
+            (
+                client.post("/add_nodes", json!({ "nodes": nodes })).await?,
+                |report| println!("{}", nodes_table(report)),
+            )
+        }
+        "remove" | "disable" | "enable" => {
+            let node = matches.value_of("node").unwrap();
+            (
+                client
+                    .post(&format!("/{}_node", command), json!({ "node": node }))
+                    .await?,
+                |report| println!("{}", nodes_table(report)),
+            )
+        }
+        "disagreements" => (client.get("/disagreements").await?, |disagreements| {
+            println!("{}", disagreements_table(disagreements))
+        }),
+        _ => return Err(matches.usage().to_string()),
+    };
+
+    if json_output {
+        println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+    } else {
+        print(&resp);
+    }
+    Ok(())
+}
+
+// `executionbackup ctl`, talks to a running EB's admin api
+// returns false if the request failed
+pub async fn run(matches: &clap::ArgMatches<'_>) -> bool {
+    match ctl(matches).await {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nodes_table() {
+        let report = json!({
+            "primary_node": "http://geth:8551",
+            "node_states": {"http://geth:8551": "synced", "http://nm:8551": "offline"},
+            "labels": {"http://geth:8551": "geth"},
+            "heads": {"http://geth:8551": {"number": "0x64", "hash": "0x01"}},
+            "response_times": {"http://geth:8551": 1500},
+            "circuit_states": {"http://geth:8551": "closed", "http://nm:8551": "open"},
+            "queue_depths": {"http://geth:8551": 0, "http://nm:8551": 3},
+            "client_versions": {"http://geth:8551": {"code": "GE", "name": "Geth", "version": "1.14.11", "commit": "0x01020304"}}
+        });
+
+        assert_eq!(
+            nodes_table(&report),
+            "NODE                LABEL  STATE    HEAD  RESPONSE  CIRCUIT  QUEUE  CLIENT\n\
+             http://geth:8551 *  geth   synced   100   1.50ms    closed   0      Geth 1.14.11\n\
+             http://nm:8551      -      offline  -     -         open     3      -"
+        );
+    }
+}
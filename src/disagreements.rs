@@ -0,0 +1,100 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use types::*;
+
+// how many disagreements are kept for the admin api
+pub const HISTORY_LEN: usize = 64;
+
+// a newPayload or fcU the voting nodes didn't all answer the same way
+#[derive(Clone, Debug, Serialize)]
+pub struct Disagreement {
+    pub method: String,
+    // the payload's block hash for newPayload, the head block hash for fcU
+    pub block_hash: Option<String>,
+    // what each node answered, by node url
+    pub votes: HashMap<String, PayloadStatusV1>,
+    // what EB answered the CL
+    pub outcome: String,
+    // unix time in milliseconds
+    pub timestamp: i64,
+}
+
+fn outcome(result: &Result<PayloadStatusV1, FcuLogicError>) -> String {
+    match result {
+        Ok(status) => match serde_json::to_value(status.status) {
+            Ok(serde_json::Value::String(status)) => status,
+            _ => format!("{:?}", status.status),
+        },
+        Err(FcuLogicError::NoMajority) => "SYNCING (no majority)".to_string(),
+        Err(FcuLogicError::OneNodeIsInvalid) => "SYNCING (a node said INVALID)".to_string(),
+        Err(FcuLogicError::NoResponses) => "SYNCING (no responses)".to_string(),
+    }
+}
+
+// None if every node answered the same
+pub fn disagreement(
+    request: &RpcRequest,
+    votes: &[(String, PayloadStatusV1)],
+    result: &Result<PayloadStatusV1, FcuLogicError>,
+) -> Option<Disagreement> {
+    let first = &votes.first()?.1;
+    if votes.iter().all(|(_, status)| status == first) {
+        return None;
+    }
+
+    let block_hash = match request.method {
+        EngineMethod::engine_forkchoiceUpdatedV1
+        | EngineMethod::engine_forkchoiceUpdatedV2
+        | EngineMethod::engine_forkchoiceUpdatedV3 => &request.params[0]["headBlockHash"],
+        _ => &request.params[0]["blockHash"],
+    };
+
+    Some(Disagreement {
+        method: request.method.name(),
+        block_hash: block_hash.as_str().map(|hash| hash.to_string()),
+        votes: votes.iter().cloned().collect(),
+        outcome: outcome(result),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_disagreement() {
+        let request: RpcRequest = serde_json::from_value(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "engine_forkchoiceUpdatedV3",
+            "params": [{"headBlockHash": "0x01"}, null]
+        }))
+        .unwrap();
+        let valid: PayloadStatusV1 = serde_json::from_value(
+            json!({"status": "VALID", "latestValidHash": null, "validationError": null}),
+        )
+        .unwrap();
+        let syncing: PayloadStatusV1 = serde_json::from_value(
+            json!({"status": "SYNCING", "latestValidHash": null, "validationError": null}),
+        )
+        .unwrap();
+
+        let agreeing = [
+            ("http://a:8551".to_string(), valid.clone()),
+            ("http://b:8551".to_string(), valid.clone()),
+        ];
+        assert!(disagreement(&request, &agreeing, &Ok(valid.clone())).is_none());
+
+        let votes = [
+            ("http://a:8551".to_string(), valid.clone()),
+            ("http://b:8551".to_string(), syncing),
+        ];
+        let disagreement = disagreement(&request, &votes, &Err(FcuLogicError::NoMajority)).unwrap();
+        assert_eq!(disagreement.method, "engine_forkchoiceUpdatedV3");
+        assert_eq!(disagreement.block_hash, Some("0x01".to_string()));
+        assert_eq!(disagreement.votes.len(), 2);
+        assert_eq!(disagreement.outcome, "SYNCING (no majority)");
+    }
+}
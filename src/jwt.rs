@@ -0,0 +1,168 @@
+use std::io::Write;
+use types::{make_jwt, read_jwt, read_jwt_secret, Claims};
+
+// how far a jwt's iat can be from now, the same as the engine api allows
+pub const JWT_IAT_LEEWAY: i64 = 60;
+
+// the claims of a jwt signed with the key, the iat isn't checked
+pub fn decode_token(
+    token: &str,
+    key: &jsonwebtoken::DecodingKey,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256);
+    validation.validate_exp = false;
+    validation.required_spec_claims.clear();
+
+    jsonwebtoken::decode::<Claims>(token, key, &validation).map(|token| token.claims)
+}
+
+// seconds between the token's iat and now, negative if it was issued in the future
+pub fn iat_drift(claims: &Claims) -> i64 {
+    chrono::Utc::now().timestamp() - claims.iat
+}
+
+// a new 32 byte secret, hex encoded the way ELs write theirs
+fn generate_secret() -> Result<String, String> {
+    let mut secret = [0u8; 32];
+    getrandom::getrandom(&mut secret).map_err(|e| format!("Could not generate secret: {}", e))?;
+    Ok(format!("0x{}", hex::encode(secret)))
+}
+
+// writes the secret so only the owner can read it
+fn write_secret(path: &str, secret: &str, overwrite: bool) -> Result<(), String> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+    if overwrite {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => {
+            format!("{} already exists, use --force to overwrite it", path)
+        }
+        _ => format!("Could not create {}: {}", path, e),
+    })?;
+
+    // the mode only applies to new files, an overwritten file is locked down while it's still empty
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Could not set permissions on {}: {}", path, e))?;
+    }
+
+    file.write_all(secret.as_bytes())
+        .map_err(|e| format!("Could not write {}: {}", path, e))?;
+    Ok(())
+}
+
+// whether the token is signed with the secret and how far its iat is from now
+fn verify_token(token: &str, secret: &[u8]) -> Result<i64, String> {
+    let token = token.trim();
+    let token = token.strip_prefix("Bearer ").unwrap_or(token);
+    let claims = decode_token(token, &jsonwebtoken::DecodingKey::from_secret(secret))
+        .map_err(|e| format!("Token is not valid for this secret: {}", e))?;
+    Ok(iat_drift(&claims))
+}
+
+fn generate(matches: &clap::ArgMatches<'_>) -> Result<bool, String> {
+    let path = matches.value_of("secret").unwrap();
+    write_secret(path, &generate_secret()?, matches.is_present("force"))?;
+    println!("Wrote a new jwt secret to {}", path);
+    Ok(true)
+}
+
+fn token(matches: &clap::ArgMatches<'_>) -> Result<bool, String> {
+    let key = read_jwt(matches.value_of("secret").unwrap())?;
+    let token = make_jwt(&key).map_err(|e| format!("Could not make jwt: {}", e))?;
+    println!("{}", token);
+    Ok(true)
+}
+
+fn verify(matches: &clap::ArgMatches<'_>) -> Result<bool, String> {
+    let secret = read_jwt_secret(matches.value_of("secret").unwrap())?;
+    let drift = verify_token(matches.value_of("token").unwrap(), &secret)?;
+
+    println!("Signature is valid");
+    match drift {
+        0.. => println!("Issued {}s ago", drift),
+        _ => println!("Issued {}s in the future", -drift),
+    }
+    if drift.abs() > JWT_IAT_LEEWAY {
+        println!(
+            "iat is more than {}s from now, ELs and EB's admin api will reject it",
+            JWT_IAT_LEEWAY
+        );
+        return Ok(false);
+    }
+    println!("iat is within the {}s ELs accept", JWT_IAT_LEEWAY);
+    Ok(true)
+}
+
+// `executionbackup jwt generate|token|verify`, returns false if the command failed or the token isn't accepted
+pub fn run(matches: &clap::ArgMatches<'_>) -> bool {
+    let result = match matches.subcommand() {
+        ("generate", Some(matches)) => generate(matches),
+        ("token", Some(matches)) => token(matches),
+        ("verify", Some(matches)) => verify(matches),
+        _ => Err(matches.usage().to_string()),
+    };
+
+    match result {
+        Ok(passed) => passed,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_and_verify() {
+        let path = std::env::temp_dir().join(format!("eb-jwt-test-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        write_secret(path, &generate_secret().unwrap(), false).unwrap();
+        // an existing secret is only replaced with --force
+        assert!(write_secret(path, &generate_secret().unwrap(), false).is_err());
+
+        let secret = read_jwt_secret(path).unwrap();
+        assert_eq!(secret.len(), 32);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let token = make_jwt(&read_jwt(path).unwrap()).unwrap();
+        assert!(verify_token(&token, &secret).unwrap().abs() <= 1);
+        assert!(verify_token(&format!("Bearer {}", token), &secret).is_ok());
+        assert!(verify_token(&token, &[0u8; 32]).is_err());
+
+        // --force on a world readable file leaves it readable only by the owner
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o644)).unwrap();
+            write_secret(path, &generate_secret().unwrap(), true).unwrap();
+            let mode = std::fs::metadata(path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            assert_ne!(read_jwt_secret(path).unwrap(), secret);
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
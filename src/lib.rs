@@ -0,0 +1,2668 @@
+use axum::{
+    self,
+    extract::{self, DefaultBodyLimit},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Router,
+};
+use ethereum_types::{H256, U256};
+use futures::future::join_all;
+
+use serde_json::json;
+use std::{
+    any::type_name,
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+use tokio::{
+    sync::{Mutex, RwLock},
+    time::Duration,
+};
+pub mod admin;
+mod blobs;
+mod capabilities;
+pub mod check;
+mod client_versions;
+pub mod config;
+pub mod ctl;
+pub mod disagreements;
+pub mod jwt;
+mod payload_bodies;
+pub mod persist;
+pub mod reload;
+mod table;
+mod upgrade;
+mod verify_hash;
+pub mod verify_payload;
+pub use types;
+use types::{circuit::CircuitBreakerConfig, node::Node, state::next_state, *};
+use verify_hash::verify_payload_block_hash;
+
+pub const VERSION: &str = "1.2.0";
+
+// json-rpc error codes, engine api errors (-38xxx) come from the nodes and are passed on as they are
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+// implementation defined, used when there's no node to answer
+const SERVER_ERROR: i64 = -32000;
+
+pub fn fork_name_at_epoch(epoch: u64, fork_config: &ForkConfig) -> ForkName {
+    if let Some(fork_epoch) = fork_config.cancun_fork_epoch {
+        if epoch >= fork_epoch {
+            return ForkName::Cancun;
+        }
+    }
+    if let Some(fork_epoch) = fork_config.shanghai_fork_epoch {
+        if epoch >= fork_epoch {
+            return ForkName::Shanghai;
+        }
+    }
+    ForkName::Merge
+}
+
+fn timestamp_to_version(timestamp: &u64, fork_config: &ForkConfig) -> Option<ForkName> {
+    // 32 slots/epoch
+    let slot = timestamp.checked_sub(1606824000)?.checked_div(12)?; // genesis time / seconds per slot
+    let epoch = slot.checked_div(32)?; // slot / slots per epoch
+    Some(fork_name_at_epoch(epoch, fork_config))
+}
+
+pub fn newpayload_serializer(
+    mut request: RpcRequest,
+    fork_config: &ForkConfig,
+) -> Result<NewPayloadRequest, String> {
+    let params = match request.params.as_array_mut() {
+        Some(params_vec) => params_vec,
+        None => {
+            tracing::error!("Could not serialize newPayload's params into a vec.");
+            return Err("Could not serialize newPayload's params into a vec".to_string());
+        }
+    };
+
+    if request.method == EngineMethod::engine_newPayloadV3 {
+        // params will have 3 fields: [ExecutionPayloadV3, expectedBlobVersionedHashes, ParentBeaconBlockRoot]
+        if params.len() != 3 {
+            tracing::error!("newPayloadV3's params did not have 3 elements.");
+            return Err("newPayloadV3's params did not have 3 elements.".to_string());
+        }
+
+        let execution_payload: ExecutionPayloadV3 = match serde_json::from_value(params[0].take()) {
+            // direct getting is safe here since we checked that we have least 3 elements
+            Ok(execution_payload) => execution_payload,
+            Err(e) => {
+                tracing::error!(
+                    "Could not serialize ExecutionPayload from newPayloadV3: {}",
+                    e
+                );
+                return Err("Could not serialize ExecutionPayload".to_string());
+            }
+        };
+
+        let versioned_hashes: Vec<H256> = match serde_json::from_value(params[1].take()) {
+            Ok(versioned_hashes) => versioned_hashes,
+            Err(e) => {
+                tracing::error!(
+                    "Could not serialize VersionedHashes from newPayloadV3: {}",
+                    e
+                );
+                return Err("Could not serialize Versioned Hashes.".to_string());
+            }
+        };
+
+        let parent_beacon_block_root: H256 = match serde_json::from_value(params[2].take()) {
+            Ok(parent_beacon_block_root) => parent_beacon_block_root,
+            Err(e) => {
+                tracing::error!(
+                    "Could not serialize ParentBeaconBlockRoot from newPayloadV3: {}",
+                    e
+                );
+                return Err("Could not serialize ParentBeaconBlockRoot.".to_string());
+            }
+        };
+
+        return Ok(NewPayloadRequest {
+            execution_payload: types::ExecutionPayload::V3(execution_payload),
+            expected_blob_versioned_hashes: Some(versioned_hashes),
+            parent_beacon_block_root: Some(parent_beacon_block_root),
+        });
+    }
+
+    // parmas will just have [ExecutionPayloadV1 | ExecutionPayloadV2]
+
+    if params.len() != 1 {
+        tracing::error!("newPayloadV1|2's params did not have anything or something went wrong (newPayloadV1|2 called with more than just 1 param (ExecutionPayload).");
+        return Err("newPayloadV1|2's params did not have anything.".to_string());
+    }
+
+    let QuantityU64 { value: timestamp } = match params[0].get("timestamp") {
+        Some(timestamp) => {
+            match serde_json::from_value(timestamp.clone()) {
+                Ok(timestamp) => timestamp,
+                Err(e) => {
+                    tracing::error!("Execution payload timestamp is not representable as u64: {}. Timestamp: {}", e, timestamp);
+                    return Err(
+                        "Execution payload timestamp is not representable as u64".to_string()
+                    );
+                }
+            }
+        }
+        None => {
+            tracing::error!("Execution payload does not have timestamp");
+            return Err("Execution payload does not have timestamp".to_string());
+        }
+    };
+
+    let fork_name = match timestamp_to_version(&timestamp, fork_config) {
+        Some(fork_name) => fork_name,
+        None => {
+            tracing::error!("Error converting execution payload timestamp to fork name");
+            return Err("Error converting execution payload timestamp to fork name".to_string());
+        }
+    };
+
+    let execution_payload = match fork_name {
+        ForkName::Merge => match serde_json::from_value::<ExecutionPayloadV1>(params[0].take()) {
+            Ok(execution_payload) => ExecutionPayload::V1(execution_payload),
+            Err(e) => {
+                tracing::error!(
+                        "Could not serialize ExecutionPayloadV1 from newPayloadV1|2; Merge fork. Error: {}",
+                        e
+                    );
+                return Err("Could not serialize ExecutionPayload.".to_string());
+            }
+        },
+        ForkName::Shanghai => {
+            match serde_json::from_value::<ExecutionPayloadV2>(params[0].take()) {
+                Ok(execution_payload) => ExecutionPayload::V2(execution_payload),
+                Err(e) => {
+                    tracing::error!(
+                        "Could not serialize ExecutionPayloadV2 from newPayloadV2; Shanghai fork. Error: {}",
+                        e
+                    );
+                    return Err("Could not serialize ExecutionPayload.".to_string());
+                }
+            }
+        }
+        ForkName::Cancun => match serde_json::from_value::<ExecutionPayloadV3>(params[0].take()) {
+            Ok(execution_payload) => ExecutionPayload::V3(execution_payload),
+            Err(e) => {
+                tracing::error!(
+                        "Could not serialize ExecutionPayloadV3 from newPayloadV3; Cancun fork. Error: {}",
+                        e
+                    );
+                return Err("Could not serialize ExecutionPayload.".to_string());
+            }
+        },
+    };
+
+    Ok(NewPayloadRequest {
+        execution_payload,
+        expected_blob_versioned_hashes: None,
+        parent_beacon_block_root: None,
+    })
+}
+
+fn make_response(id: &serde_json::Value, result: serde_json::Value) -> String {
+    json!({"jsonrpc":"2.0","id":id,"result":result}).to_string()
+}
+
+fn make_error(id: &serde_json::Value, code: i64, error: &str) -> String {
+    make_el_error(id, &json!({"code": code, "message": error}))
+}
+
+// an error object from a node, with the caller's id
+fn make_el_error(id: &serde_json::Value, error: &serde_json::Value) -> String {
+    json!({"jsonrpc": "2.0", "id": id, "error": error}).to_string()
+}
+
+// the error to pass on when no node returned a result and every node that answered returned the same error code
+fn agreed_error(errors: &[serde_json::Value]) -> Option<serde_json::Value> {
+    let first = errors.first()?;
+    errors
+        .iter()
+        .all(|error| error.get("code") == first.get("code"))
+        .then(|| first.clone())
+}
+
+// the id of a raw request, null if it has none or isn't json
+fn request_id(request: &str) -> serde_json::Value {
+    serde_json::from_str::<serde_json::Value>(request)
+        .ok()
+        .and_then(|request| request.get("id").cloned())
+        .unwrap_or(serde_json::Value::Null)
+}
+
+fn parse_result(resp: &str) -> Result<serde_json::Value, ParseError> {
+    let j = match serde_json::from_str::<serde_json::Value>(resp) {
+        Ok(j) => j,
+        Err(e) => {
+            tracing::error!(reponse_body = ?resp, "Error deserializing response: {}", e);
+            return Err(ParseError::InvalidJson);
+        }
+    };
+
+    if let Some(error) = j.get("error") {
+        tracing::error!(reponse_body = ?resp, "Response has error: {}", error);
+        return Err(ParseError::ElError(error.clone()));
+    }
+
+    let result = match j.get("result") {
+        Some(result) => result,
+        None => {
+            tracing::error!(reponse_body = ?resp, "Response has no result field");
+            return Err(ParseError::MethodNotFound);
+        }
+    };
+
+    Ok(result.clone())
+}
+
+fn make_syncing_str(
+    id: &serde_json::Value,
+    payload: &serde_json::Value,
+    method: &EngineMethod,
+    parent_beacon_block_root: Option<H256>,
+) -> String {
+    match method {
+        EngineMethod::engine_newPayloadV1 | EngineMethod::engine_newPayloadV2 | EngineMethod::engine_newPayloadV3 => {
+            tracing::debug!(
+                "Verifying execution payload blockhash {}.",
+                payload["blockHash"]
+            );
+
+            let execution_payload = match method {
+                EngineMethod::engine_newPayloadV1 => match serde_json::from_value::<ExecutionPayloadV1>(payload.clone()) {
+                        Ok(execution_payload) => ExecutionPayload::V1(execution_payload),
+                        Err(e) => {
+                            tracing::error!("Error deserializing execution payload: {}", e);
+                            return make_error(id, INVALID_PARAMS, &e.to_string());
+                        }
+                    },
+
+                EngineMethod::engine_newPayloadV2 => match serde_json::from_value::<ExecutionPayloadV2>(payload.clone()) {
+                        Ok(execution_payload) => ExecutionPayload::V2(execution_payload),
+                        Err(e) => {
+                            tracing::error!("Error deserializing execution payload: {}", e);
+                            return make_error(id, INVALID_PARAMS, &e.to_string());
+                        }
+                    },
+
+                EngineMethod::engine_newPayloadV3 => match serde_json::from_value::<ExecutionPayloadV3>(payload.clone()) {
+                        Ok(execution_payload) => ExecutionPayload::V3(execution_payload),
+                        Err(e) => {
+                            tracing::error!("Error deserializing execution payload: {}", e);
+                            return make_error(id, INVALID_PARAMS, &e.to_string());
+                        }
+                    },
+                _ => unreachable!("File a issue on Github. This should never happen. Matched non-newPayload inside previously matched newPayload"),
+            };
+
+            // the CL expects a mismatched hash as a payload status, not a json-rpc error
+            if let Err(e) = verify_payload_block_hash(&execution_payload, parent_beacon_block_root) {
+                tracing::error!("Error verifying execution payload blockhash: {}", e);
+                return make_response(id, json!({"status":"INVALID_BLOCK_HASH","latestValidHash":null,"validationError":e.to_string()}));
+            }
+
+            tracing::debug!(
+                "Execution payload blockhash {} verified. Returning SYNCING",
+                payload["blockHash"]
+            );
+            json!({"result":{"latestValidHash":null,"status":"SYNCING","validationError":null},"id":id,"jsonrpc":"2.0"}).to_string()
+        },
+
+        EngineMethod::engine_forkchoiceUpdatedV1 | EngineMethod::engine_forkchoiceUpdatedV2 | EngineMethod::engine_forkchoiceUpdatedV3 => {
+            json!({"jsonrpc":"2.0","id":id,"result":{"payloadStatus":{"status":"SYNCING","latestValidHash":null,"validationError":null}},"payloadId":null}).to_string()
+        },
+
+        _ => {
+            make_error(id, INTERNAL_ERROR, "Called make_syncing_str with a non fcu or newpayload request")
+        }
+    }
+}
+
+// the answers to a request sent to several nodes
+struct Responses<T> {
+    results: Vec<T>,
+    // set when no node returned a result and the ones that answered agree on the error
+    agreed_error: Option<serde_json::Value>,
+}
+
+#[derive(Clone)]
+pub struct RouterSettings {
+    // percentage of nodes that need to agree for it to be deemed a majority
+    pub majority_percentage: f32, // 0.1..0.9
+
+    // setting to set if node timings are displayed
+    pub node_timings_enabled: bool,
+
+    // how many blocks a synced node's head can be behind the highest head before it's considered lagging
+    pub max_head_lag: u64,
+
+    // when to stop sending engine calls to a node that keeps failing them
+    pub circuit_config: CircuitBreakerConfig,
+
+    // for nodes added without their own timeout
+    pub request_timeout: Duration,
+
+    // time between health checks of all nodes
+    pub recheck_interval: Duration,
+
+    // how engine methods EB doesn't know are routed
+    pub method_strategies: config::MethodStrategies,
+
+    // how the nodes' capabilities are combined for the CL
+    pub capabilities_mode: config::CapabilitiesMode,
+}
+
+// the same as the command line's defaults
+impl Default for RouterSettings {
+    fn default() -> Self {
+        RouterSettings {
+            majority_percentage: 0.6,
+            node_timings_enabled: false,
+            max_head_lag: 8,
+            circuit_config: CircuitBreakerConfig {
+                failure_threshold: 5,
+                open_duration: Duration::from_secs(30),
+            },
+            request_timeout: Duration::from_millis(7500),
+            recheck_interval: Duration::from_secs(15),
+            method_strategies: config::MethodStrategies::default(),
+            capabilities_mode: config::CapabilitiesMode::default(),
+        }
+    }
+}
+
+impl RouterSettings {
+    pub fn new(settings: &config::Settings) -> Self {
+        RouterSettings {
+            majority_percentage: settings.fcu_majority,
+            node_timings_enabled: settings.node_timings_enabled,
+            max_head_lag: settings.max_head_lag,
+            circuit_config: settings.circuit_config,
+            request_timeout: settings.request_timeout,
+            recheck_interval: settings.recheck_interval,
+            method_strategies: settings.method_strategies.clone(),
+            capabilities_mode: settings.capabilities_mode,
+        }
+    }
+}
+
+pub struct NodeRouter {
+    nodes: Arc<Mutex<Vec<Arc<Node>>>>,
+    alive_nodes: Arc<RwLock<Vec<Arc<Node>>>>,
+    dead_nodes: Arc<RwLock<Vec<Arc<Node>>>>,
+    alive_but_syncing_nodes: Arc<RwLock<Vec<Arc<Node>>>>,
+
+    // this node will be the selected primary node used to route all requests
+    primary_node: Arc<RwLock<Arc<Node>>>,
+
+    // jwt encoded key used to make tokens for the EE's auth port
+    // jwt_key: Arc<jsonwebtoken::EncodingKey>,
+    // replaced when the config is reloaded
+    settings: std::sync::RwLock<RouterSettings>,
+
+    fork_config: ForkConfig,
+
+    // for if we want to use a general jwt with /create_node
+    general_jwt: std::sync::RwLock<Option<jsonwebtoken::EncodingKey>>,
+
+    // rolling upgrades by node url, finished ones are kept to report on
+    upgrades: RwLock<HashMap<String, upgrade::UpgradeProgress>>,
+
+    // recent newPayload and fcU requests to replay to nodes coming back from an upgrade
+    recent_payloads: Mutex<VecDeque<RpcRequest>>,
+
+    // where nodes added and removed at runtime are saved, if anywhere
+    node_store: Option<persist::NodeStore>,
+
+    // only set when the router was made from the command line, there's nothing to reload otherwise
+    reloader: Option<reload::Reloader>,
+
+    // checks requests to the admin api, replaced when the config is reloaded
+    // without one every admin request is rejected
+    admin_auth: std::sync::RwLock<Option<admin::AdminAuth>>,
+
+    // how many of the blobs asked for each node had, by node url
+    blob_hits: std::sync::Mutex<HashMap<String, BlobHits>>,
+
+    // the engine methods each node said it supports in its last engine_exchangeCapabilities response, by node url
+    capabilities: std::sync::RwLock<HashMap<String, Vec<String>>>,
+
+    // recent newPayload and fcU requests the voting nodes didn't agree on
+    disagreements: std::sync::Mutex<VecDeque<disagreements::Disagreement>>,
+}
+
+// builds a NodeRouter, only the nodes are needed, everything else has a default
+pub struct RouterBuilder {
+    nodes: Vec<Arc<Node>>,
+    settings: RouterSettings,
+    fork_config: ForkConfig,
+    general_jwt: Option<jsonwebtoken::EncodingKey>,
+    node_store: Option<persist::NodeStore>,
+    reloader: Option<reload::Reloader>,
+    admin_auth: Option<admin::AdminAuth>,
+}
+
+impl RouterBuilder {
+    pub fn settings(mut self, settings: RouterSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    // mainnet if not set
+    pub fn fork_config(mut self, fork_config: ForkConfig) -> Self {
+        self.fork_config = fork_config;
+        self
+    }
+
+    // signs requests to nodes added through the admin api without their own jwt secret
+    pub fn general_jwt(mut self, general_jwt: jsonwebtoken::EncodingKey) -> Self {
+        self.general_jwt = Some(general_jwt);
+        self
+    }
+
+    // where nodes added and removed at runtime are saved
+    pub fn node_store(mut self, node_store: persist::NodeStore) -> Self {
+        self.node_store = Some(node_store);
+        self
+    }
+
+    pub fn reloader(mut self, reloader: reload::Reloader) -> Self {
+        self.reloader = Some(reloader);
+        self
+    }
+
+    pub fn admin_auth(mut self, admin_auth: admin::AdminAuth) -> Self {
+        self.admin_auth = Some(admin_auth);
+        self
+    }
+
+    pub fn build(self) -> Result<NodeRouter, String> {
+        // there always has to be a primary node
+        let first_node = self
+            .nodes
+            .first()
+            .cloned()
+            .ok_or("A router needs at least one node")?;
+
+        Ok(NodeRouter {
+            nodes: Arc::new(Mutex::new(self.nodes)),
+            alive_nodes: Arc::new(RwLock::new(Vec::new())),
+            dead_nodes: Arc::new(RwLock::new(Vec::new())),
+            alive_but_syncing_nodes: Arc::new(RwLock::new(Vec::new())),
+            // the first node until the first recheck picks one
+            primary_node: Arc::new(RwLock::new(first_node)),
+            settings: std::sync::RwLock::new(self.settings),
+            fork_config: self.fork_config,
+            general_jwt: std::sync::RwLock::new(self.general_jwt),
+            upgrades: RwLock::new(HashMap::new()),
+            recent_payloads: Mutex::new(VecDeque::with_capacity(upgrade::REPLAY_LEN)),
+            node_store: self.node_store,
+            reloader: self.reloader,
+            admin_auth: std::sync::RwLock::new(self.admin_auth),
+            blob_hits: std::sync::Mutex::new(HashMap::new()),
+            capabilities: std::sync::RwLock::new(HashMap::new()),
+            disagreements: std::sync::Mutex::new(VecDeque::with_capacity(
+                disagreements::HISTORY_LEN,
+            )),
+        })
+    }
+}
+
+impl NodeRouter {
+    pub fn builder(nodes: Vec<Arc<Node>>) -> RouterBuilder {
+        RouterBuilder {
+            nodes,
+            settings: RouterSettings::default(),
+            fork_config: ForkConfig::mainnet(),
+            general_jwt: None,
+            node_store: None,
+            reloader: None,
+            admin_auth: None,
+        }
+    }
+
+    pub fn settings(&self) -> RouterSettings {
+        self.settings.read().unwrap().clone()
+    }
+
+    pub async fn nodes(&self) -> Vec<Arc<Node>> {
+        self.nodes.lock().await.clone()
+    }
+
+    pub async fn primary_node(&self) -> Arc<Node> {
+        self.primary_node.read().await.clone()
+    }
+
+    // the same report as the admin api's /metrics
+    pub async fn metrics_report(self: &Arc<Self>) -> Result<serde_json::Value, serde_json::Error> {
+        make_metrics_report(self.clone()).await
+    }
+
+    // recent newPayload and fcU requests the voting nodes didn't agree on, oldest first
+    pub fn disagreements(&self) -> Vec<disagreements::Disagreement> {
+        self.disagreements.lock().unwrap().iter().cloned().collect()
+    }
+
+    // routes a json-rpc request or batch the way the engine port does
+    // authorization is the CL's Authorization header, it's passed on to the nodes for engine requests
+    pub async fn handle_request(
+        self: &Arc<Self>,
+        body: String,
+        authorization: Option<&str>,
+    ) -> (String, u16) {
+        let mut headers = HeaderMap::new();
+        if let Some(authorization) = authorization {
+            match HeaderValue::from_str(authorization) {
+                Ok(authorization) => {
+                    headers.insert(header::AUTHORIZATION, authorization);
+                }
+                Err(_) => {
+                    return (
+                        make_error(
+                            &request_id(&body),
+                            INVALID_REQUEST,
+                            "Invalid Authorization header",
+                        ),
+                        400,
+                    )
+                }
+            }
+        }
+
+        let resp = route_body(&headers, self, body).await;
+        (resp.body().clone(), resp.status().as_u16())
+    }
+
+    // routes an engine request that's already parsed, skipping the json-rpc checks handle_request does
+    pub async fn engine_request(
+        &self,
+        request: &RpcRequest,
+        authorization: String,
+    ) -> (String, u16) {
+        self.do_engine_route(&self.fork_config, request, authorization)
+            .await
+    }
+
+    fn method_strategy(&self, method: &str) -> config::MethodStrategy {
+        self.settings.read().unwrap().method_strategies.get(method)
+    }
+
+    // nodes that haven't been asked for their capabilities yet are sent everything
+    fn supports(&self, node: &Node, method: &str) -> bool {
+        method == "engine_exchangeCapabilities"
+            || self
+                .capabilities
+                .read()
+                .unwrap()
+                .get(&node.url)
+                .is_none_or(|methods| methods.iter().any(|m| m == method))
+    }
+
+    fn general_jwt(&self) -> Option<jsonwebtoken::EncodingKey> {
+        self.general_jwt.read().unwrap().clone()
+    }
+
+    // settings() clones everything, this is read for every node on every engine call
+    fn circuit_config(&self) -> CircuitBreakerConfig {
+        self.settings.read().unwrap().circuit_config
+    }
+
+    async fn record_payload(&self, request: &RpcRequest) {
+        let mut recent_payloads = self.recent_payloads.lock().await;
+        if recent_payloads.len() == upgrade::REPLAY_LEN {
+            recent_payloads.pop_front();
+        }
+        recent_payloads.push_back(upgrade::replayable(request));
+    }
+
+    // the head most alive nodes have
+    async fn majority_head(&self) -> Option<BlockHead> {
+        let alive_nodes = self.alive_nodes.read().await.clone();
+        let mut head_counts: HashMap<BlockHead, usize> = HashMap::new();
+        for node in alive_nodes.iter() {
+            if let Some(head) = node.status.read().await.head {
+                *head_counts.entry(head).or_insert(0) += 1;
+            }
+        }
+
+        head_counts
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(head, _)| head)
+    }
+
+    async fn make_node_syncing(&self, node: Arc<Node>, reason: &str) {
+        let mut alive_nodes = self.alive_nodes.write().await;
+        let index = alive_nodes.iter().position(|x| *x.url == node.url);
+        let index = match index {
+            Some(index) => index,
+            None => {
+                // node is not in alive_nodes, so it's in syncing or worse, stop here
+                return;
+            }
+        };
+
+        let mut alive_but_syncing_nodes = self.alive_but_syncing_nodes.write().await;
+        alive_nodes.remove(index);
+        alive_but_syncing_nodes.push(node.clone());
+        node.transition(SyncingStatus::OnlineAndSyncing, reason)
+            .await;
+    }
+
+    // takes the node out of the alive and syncing nodes so it isn't sent new requests, ones already sent can finish
+    // it stays out until a recheck moves it back, which doesn't happen for maintenance
+    async fn take_out_of_rotation(&self, node: &Arc<Node>, state: SyncingStatus, reason: &str) {
+        let mut alive_but_syncing_nodes = self.alive_but_syncing_nodes.write().await;
+        let mut alive_nodes = self.alive_nodes.write().await;
+        let mut dead_nodes = self.dead_nodes.write().await;
+
+        alive_nodes.retain(|alive_node| alive_node.url != node.url);
+        alive_but_syncing_nodes.retain(|syncing_node| syncing_node.url != node.url);
+        if !dead_nodes.iter().any(|dead_node| dead_node.url == node.url) {
+            dead_nodes.push(node.clone());
+        }
+        drop(dead_nodes);
+        drop(alive_nodes);
+        drop(alive_but_syncing_nodes);
+
+        node.transition(state, reason).await;
+    }
+
+    async fn find_node(&self, name: &str) -> Option<Arc<Node>> {
+        self.nodes
+            .lock()
+            .await
+            .iter()
+            .find(|node| node.matches(name))
+            .cloned()
+    }
+
+    // stops sending the node new requests until it's enabled again
+    pub async fn disable_node(&self, name: &str) -> Result<Arc<Node>, NodeAdminError> {
+        let node = self.find_node(name).await.ok_or(NodeAdminError::NotFound)?;
+
+        self.take_out_of_rotation(&node, SyncingStatus::Maintenance, "disabled by admin")
+            .await;
+        Ok(node)
+    }
+
+    // the next recheck decides what state the node is in
+    pub async fn enable_node(&self, name: &str) -> Result<Arc<Node>, NodeAdminError> {
+        let node = self.find_node(name).await.ok_or(NodeAdminError::NotFound)?;
+
+        if node.status.read().await.status != SyncingStatus::Maintenance {
+            return Err(NodeAdminError::NotDisabled);
+        }
+
+        node.transition(
+            SyncingStatus::NodeNotInitialized,
+            "enabled by admin, waiting for health check",
+        )
+        .await;
+        Ok(node)
+    }
+
+    pub async fn remove_node(&self, name: &str) -> Result<Arc<Node>, NodeAdminError> {
+        let mut nodes = self.nodes.lock().await;
+        let index = nodes
+            .iter()
+            .position(|node| node.matches(name))
+            .ok_or(NodeAdminError::NotFound)?;
+
+        // we always need a primary node
+        if nodes.len() == 1 {
+            return Err(NodeAdminError::LastNode);
+        }
+
+        let node = nodes.remove(index);
+        drop(nodes);
+
+        self.take_out_of_rotation(&node, SyncingStatus::Maintenance, "removed by admin")
+            .await;
+        self.dead_nodes
+            .write()
+            .await
+            .retain(|dead_node| dead_node.url != node.url);
+
+        if let Some(node_store) = &self.node_store {
+            if let Err(e) = node_store.record_removed(&node.url).await {
+                tracing::error!("Unable to save removed node: {}", e);
+            }
+        }
+
+        tracing::info!("Removed node {}", node.url);
+        Ok(node)
+    }
+
+    fn record_engine_success(&self, node: &Node) {
+        if node.circuit.record_success().is_some() {
+            tracing::info!(
+                "Circuit for {} closed, node is answering engine calls again",
+                node.url
+            );
+        }
+    }
+
+    async fn record_engine_failure(&self, node: &Arc<Node>) {
+        let circuit_config = self.circuit_config();
+        if node.circuit.record_failure(&circuit_config).is_some() {
+            let reason = format!(
+                "circuit opened after failed engine calls, probing again in {:?}",
+                circuit_config.open_duration
+            );
+            self.take_out_of_rotation(node, SyncingStatus::Quarantined, &reason)
+                .await;
+        }
+    }
+
+    // returns Vec<T> where it tries to deserialize for each resp to T
+    async fn concurrent_requests<T>(
+        &self,
+        request: &RpcRequest,
+        jwt_token: String,
+        use_syncing_nodes: bool,
+    ) -> Responses<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let resps = self
+            .concurrent_node_requests(request, jwt_token, use_syncing_nodes)
+            .await;
+        Responses {
+            results: resps.results.into_iter().map(|(_, resp)| resp).collect(),
+            agreed_error: resps.agreed_error,
+        }
+    }
+
+    // like concurrent_requests, but each response comes with the weight of the node's vote
+    async fn concurrent_votes<T>(
+        &self,
+        request: &RpcRequest,
+        jwt_token: String,
+    ) -> Responses<(Arc<Node>, T)>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.concurrent_node_requests(request, jwt_token, false)
+            .await
+    }
+
+    // returns each node alongside its response deserialized to T
+    async fn concurrent_node_requests<T>(&self, request: &RpcRequest, jwt_token: String, use_syncing_nodes: bool) -> Responses<(Arc<Node>, T)>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let mut syncing_size: usize = 0;
+        let mut nodes = self.alive_nodes.read().await.clone();
+
+        if use_syncing_nodes {
+            let mut syncing_nodes = self.alive_but_syncing_nodes.read().await.clone();
+            syncing_size += syncing_nodes.len();
+            nodes.append(&mut syncing_nodes);
+        }
+
+
+        // only send the method to nodes that support it
+        let method = request.method.name();
+        nodes.retain(|node| self.supports(node, &method));
+
+        // don't send anything to nodes with an open circuit
+        let circuit_config = self.circuit_config();
+        nodes.retain(|node| node.circuit.allow_request(&circuit_config));
+
+        let mut futs = Vec::with_capacity(nodes.len() + syncing_size);
+
+        nodes
+            .iter()
+            .for_each(|node| futs.push(node.do_request(request, jwt_token.clone())));
+
+        let mut out = Vec::with_capacity(nodes.len());
+        let mut errors = Vec::new();
+        let completed = join_all(futs).await;
+        
+
+        for (node, resp) in nodes.iter().zip(completed) {
+            match resp {
+                Ok(resp) => {
+                    // response from node
+                    let result = match parse_result(&resp.0) {
+                        Ok(result) => {
+                            self.record_engine_success(node);
+                            result
+                        }
+                        Err(e) => {
+                            match e {
+                                // the node answered, it just didn't like the request
+                                ParseError::ElError(ref error) => {
+                                    self.record_engine_success(node);
+                                    errors.push(error.clone());
+                                }
+                                _ => self.record_engine_failure(node).await,
+                            }
+
+                            tracing::error!(
+                                "Couldn't parse node result for {:?}: {:?}",
+                                request.method,
+                                e
+                            );
+                            continue;
+                        }
+                    };
+
+                    match serde_json::from_value::<T>(result) {
+                        Ok(deserialized) => {
+                            out.push((node.clone(), deserialized));
+                        }
+                        Err(e) => {
+                            tracing::error!(
+                                "Couldn't deserialize response {:?} from node to type {}: {}",
+                                request.method,
+                                type_name::<T>(),
+                                e
+                            );
+                        }
+                    }
+                }
+                Err(e) => {
+                    self.record_engine_failure(node).await;
+                    tracing::error!("{:?} error: {}", request.method, e);
+                }
+            }
+        }
+
+        Responses {
+            agreed_error: match out.is_empty() {
+                true => agreed_error(&errors),
+                false => None,
+            },
+            results: out,
+        }
+    }
+
+    pub async fn recheck(&self) {
+        // check the status of all nodes
+        // order nodes in alive_nodes vector by response time
+        // dont clone nodes, just clone the Arcs
+
+        let nodes = self.nodes.lock().await;
+        let mut new_alive_nodes = Vec::<(u128, Arc<Node>)>::with_capacity(nodes.len()); // resp time, node
+        let mut new_dead_nodes = Vec::<Arc<Node>>::with_capacity(nodes.len());
+        let mut new_alive_but_syncing_nodes = Vec::<Arc<Node>>::with_capacity(nodes.len());
+
+        let mut checks = Vec::new();
+
+        for node in nodes.iter() {
+            let check = async move {
+                // nodes on another chain never get to vote, whatever they say about syncing
+                let chain_check = node
+                    .verify_chain(self.fork_config.chain_id, self.fork_config.genesis_hash)
+                    .await;
+                if let Err(ChainCheckError::WrongChain(reason)) = chain_check {
+                    tracing::error!("Refusing node {}: {}", node.url, reason);
+                    return (
+                        NodeHealth {
+                            status: SyncingStatus::WrongChain,
+                            resp_time: 0,
+                            head: None,
+                        },
+                        Some(reason),
+                        node.clone(),
+                    );
+                }
+
+                match node.check_status().await {
+                    // answering isn't enough, a node that hasn't shown which chain it's on stays out until it does
+                    Ok(status) if status.status != SyncingStatus::AuthFailed => match chain_check {
+                        Err(e) => {
+                            tracing::warn!("Holding node {} out: {}", node.url, e);
+                            (
+                                NodeHealth {
+                                    status: SyncingStatus::ChainUnverified,
+                                    ..status
+                                },
+                                Some(e.to_string()),
+                                node.clone(),
+                            )
+                        }
+                        Ok(()) => (status, None, node.clone()),
+                    },
+                    Ok(status) => (status, None, node.clone()),
+                    Err(e) => {
+                        if e.is_decode() {
+                            tracing::error!(
+                                "Error while checking node {}: {}; Maybe jwt related?",
+                                node.url,
+                                e
+                            );
+                        } else {
+                            tracing::error!("Error while checking node {}: {}", node.url, e);
+                        }
+
+                        (
+                            NodeHealth {
+                                status: SyncingStatus::Offline,
+                                resp_time: 0,
+                                head: None,
+                            },
+                            Some(e.to_string()),
+                            node.clone(),
+                        )
+                    }
+                }
+            };
+            checks.push(check);
+        }
+
+        let results = join_all(checks).await;
+
+        // synced nodes are compared against the highest head any of them has
+        let highest_head = results
+            .iter()
+            .filter(|(status, _, _)| status.status == SyncingStatus::Synced)
+            .filter_map(|(status, _, _)| status.head.map(|head| head.number))
+            .max();
+
+        let settings = self.settings();
+        for (status, error, node) in results {
+            let blocks_behind = match (highest_head, status.head) {
+                (Some(highest_head), Some(head)) => highest_head.saturating_sub(head.number),
+                _ => 0,
+            };
+
+            let state = next_state(
+                node.status.read().await.status,
+                status.status,
+                blocks_behind > settings.max_head_lag,
+                node.circuit.is_open(&settings.circuit_config),
+            );
+
+            let reason = match state {
+                SyncingStatus::Synced => "eth_syncing reports it's synced".to_string(),
+                SyncingStatus::OnlineAndSyncing => "eth_syncing reports it's syncing".to_string(),
+                SyncingStatus::Lagging => {
+                    format!("{} blocks behind the highest head", blocks_behind)
+                }
+                SyncingStatus::AuthFailed => "rejected our jwt".to_string(),
+                SyncingStatus::Quarantined => "circuit is still open".to_string(),
+                _ => error.unwrap_or_else(|| "health check failed".to_string()),
+            };
+            node.transition(state, &reason).await;
+
+            if state == SyncingStatus::Synced {
+                new_alive_nodes.push((status.resp_time, node.clone()));
+
+                if settings.node_timings_enabled {
+                    tracing::info!("{}: {:.2}ms", node.url, (status.resp_time as f64 / 1000.0));
+                    // resp_time is in micros
+                }
+            } else if state == SyncingStatus::OnlineAndSyncing || state == SyncingStatus::Lagging {
+                // lagging nodes are kept out of voting and primary selection, but get requests to catch up like syncing nodes
+                new_alive_but_syncing_nodes.push(node.clone());
+
+                if settings.node_timings_enabled {
+                    tracing::info!("{}: {:.2}ms", node.url, (status.resp_time as f64 / 1000.0));
+                }
+            } else {
+                new_dead_nodes.push(node.clone());
+                if settings.node_timings_enabled {
+                    tracing::warn!("Dead node: {}", node.url);
+                }
+            }
+        }
+
+        // sort alive_nodes by role, then response time
+        new_alive_nodes.sort_by_key(|a| (a.1.role.rank(), a.0));
+
+        // update primary node to be the first alive node
+        let mut primary_node = self.primary_node.write().await;
+        *primary_node = match new_alive_nodes.first() {
+            Some(node) => node.1.clone(),
+            None => {
+                // if there are no alive nodes, then set the primary node to a syncing node
+                match new_alive_but_syncing_nodes.first() {
+                    Some(node) => node.clone(),
+                    None => {
+                        // if there are no syncing nodes, then set the primary node to a dead node
+                        match new_dead_nodes.first() {
+                            Some(node) => node.clone(),
+                            None => {
+                                // if there are no dead nodes, then set the primary node to the first node
+                                nodes[0].clone()
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        drop(primary_node);
+
+        // lock alive_nodes, dead_nodes, and alive_but_syncing_nodes
+        let mut alive_but_syncing_nodes = self.alive_but_syncing_nodes.write().await; // we have a hard time acquiring this lock for some reason
+        let mut alive_nodes = self.alive_nodes.write().await;
+        let mut dead_nodes = self.dead_nodes.write().await;
+
+        // clear vectors and for alive nodes put the Arc<Node> in the vector
+        alive_nodes.clear();
+        dead_nodes.clear();
+        alive_but_syncing_nodes.clear();
+
+        for (_, node) in new_alive_nodes.iter() {
+            alive_nodes.push(node.clone());
+        }
+
+        for node in new_dead_nodes.iter() {
+            dead_nodes.push(node.clone());
+        }
+
+        for node in new_alive_but_syncing_nodes.iter() {
+            alive_but_syncing_nodes.push(node.clone());
+        }
+    }
+
+    // the primary node, unless it doesn't support the method and another alive node does
+    async fn get_execution_node_for(&self, method: &EngineMethod) -> Option<Arc<Node>> {
+        let primary_node = self.get_execution_node().await?;
+        let method = method.name();
+        if self.supports(&primary_node, &method) {
+            return Some(primary_node);
+        }
+
+        let node = self
+            .alive_nodes
+            .read()
+            .await
+            .iter()
+            .find(|node| self.supports(node, &method))
+            .cloned();
+        Some(node.unwrap_or(primary_node))
+    }
+
+    // try and return the primary node asap
+    // if the primary node is offline, then we'll get the next node in the vector, and set the primary node to that node (if its online)
+    // basically, return the node closest to the start of the vector that is online, and set that as the primary node
+    // if there are no online nodes, try to use a syncing node
+    // if there are no syncing nodes, return None
+    async fn get_execution_node(&self) -> Option<Arc<Node>> {
+        let primary_node = self.primary_node.read().await;
+
+        let circuit_config = self.circuit_config();
+        if primary_node.status.read().await.status == SyncingStatus::Synced
+            && !primary_node.circuit.is_open(&circuit_config)
+        {
+            return Some(primary_node.clone());
+        }
+
+        let old_primary_node_url = primary_node.url.clone(); // we're going to change it
+        drop(primary_node);
+
+        let alive_nodes = self.alive_nodes.read().await;
+
+        if alive_nodes.is_empty() {
+            let alive_but_syncing_nodes = self.alive_but_syncing_nodes.read().await;
+            if alive_but_syncing_nodes.is_empty() {
+                None
+            } else {
+                // no synced nodes, but there are syncing nodes, so return the first syncing node
+
+                let node = alive_but_syncing_nodes[0].clone();
+                let mut primary_node = self.primary_node.write().await;
+                *primary_node = node.clone();
+                Some(node)
+            }
+        } else {
+            // there are synced nodes, so return the synced node (making sure its not the already checked primary node)
+            for node in alive_nodes.iter() {
+                if node.url != old_primary_node_url && !node.circuit.is_open(&circuit_config) {
+                    let node = node.clone();
+                    let mut primary_node = self.primary_node.write().await;
+                    *primary_node = node.clone();
+                    return Some(node);
+                }
+            }
+            // no synced nodes that are not the primary node, so return a syncing node
+            let alive_but_syncing_nodes = self.alive_but_syncing_nodes.read().await;
+            if alive_but_syncing_nodes.is_empty() {
+                // no synced or syncing nodes, so return None
+                None
+            } else {
+                // no synced nodes, but there are syncing nodes, so return the first syncing node
+
+                let node = alive_but_syncing_nodes[0].clone();
+                let mut primary_node = self.primary_node.write().await;
+                *primary_node = node.clone();
+                Some(node)
+            }
+        }
+    }
+
+    // gets the majority response from a vector of respon   ses
+    // must have at least majority_percentage of the nodes agree
+    // if there is no majority, then return None
+    // if there is a draw, just return the first response
+    // each response counts as many times as the weight of the node it came from
+    fn fcu_majority(&self, results: &[(PayloadStatusV1, u32)]) -> Option<PayloadStatusV1> {
+        let total_weight: u32 = results.iter().map(|(_, weight)| weight).sum();
+        let majority_count = (total_weight as f32 * self.settings().majority_percentage) as u32;
+
+        // Create a hashmap to store response frequencies
+        let mut response_counts: HashMap<&PayloadStatusV1, u32> = HashMap::new();
+
+        for (response, weight) in results.iter() {
+            *response_counts.entry(response).or_insert(0) += weight;
+        }
+
+        // Find the response with the most occurrences
+        let mut majority_response = None;
+        let mut max_count = 0;
+
+        for (response, &count) in response_counts.iter() {
+            if count > max_count {
+                majority_response = Some(response);
+                max_count = count;
+            }
+        }
+
+        // Check if the majority count is greater than or equal to the required count
+        if max_count >= majority_count {
+            majority_response.cloned().cloned()
+        } else {
+            None
+        }
+    }
+
+    pub async fn fcu_logic(
+        &self,
+        resps: &[(Arc<Node>, PayloadStatusV1)],
+        req: &RpcRequest,
+    ) -> Result<PayloadStatusV1, FcuLogicError> {
+        let votes: Vec<(PayloadStatusV1, u32)> = resps
+            .iter()
+            .map(|(node, status)| (status.clone(), node.weight))
+            .collect();
+        let result = self.fcu_vote(&votes, req).await;
+
+        let node_statuses: Vec<(String, PayloadStatusV1)> = resps
+            .iter()
+            .map(|(node, status)| (node.url.clone(), status.clone()))
+            .collect();
+        if let Some(disagreement) = disagreements::disagreement(req, &node_statuses, &result) {
+            tracing::warn!(
+                "Nodes disagreed on {} for block {}, answered {}",
+                disagreement.method,
+                disagreement.block_hash.as_deref().unwrap_or("unknown"),
+                disagreement.outcome
+            );
+            let mut disagreements = self.disagreements.lock().unwrap();
+            if disagreements.len() == disagreements::HISTORY_LEN {
+                disagreements.pop_front();
+            }
+            disagreements.push_back(disagreement);
+        }
+
+        result
+    }
+
+    async fn fcu_vote(
+        &self,
+        resps: &[(PayloadStatusV1, u32)],
+        req: &RpcRequest,
+    ) -> Result<PayloadStatusV1, FcuLogicError> {
+        // nodes with no weight don't vote
+        let resps: Vec<(PayloadStatusV1, u32)> = resps
+            .iter()
+            .filter(|(_, weight)| *weight > 0)
+            .cloned()
+            .collect();
+
+        if resps.is_empty() {
+            // no responses, so return SYNCING
+            tracing::error!("No responses, returning SYNCING.");
+            return Err(FcuLogicError::NoResponses);
+        }
+
+        let majority = match self.fcu_majority(&resps) {
+            Some(majority) => majority,
+            None => {
+                // no majority, so return SYNCING
+                tracing::error!("No majority, returning SYNCING.");
+                return Err(FcuLogicError::NoMajority);
+            }
+        };
+
+        match majority.status {
+            PayloadStatusV1Status::Invalid | PayloadStatusV1Status::InvalidBlockHash => {
+                // majority is INVALID, so return INVALID (to not go through the next parts of the algorithm)
+                return Ok(majority); // return Ok since this is not an error
+            }
+            _ => {} // there still can be invalid in the responses
+        }
+
+        for (resp, _) in resps.iter() {
+            // check if any of the responses are INVALID
+
+            match resp.status {
+                PayloadStatusV1Status::Invalid | PayloadStatusV1Status::InvalidBlockHash => {
+                    // a response is INVALID. One node could be right, no risks, return syncing to stall CL
+                    return Err(FcuLogicError::OneNodeIsInvalid);
+                }
+                _ => {}
+            }
+        }
+
+        // queue for the syncing nodes to help them catch up, so we don't have to wait for them
+        let syncing_nodes = self.alive_but_syncing_nodes.read().await;
+        tracing::debug!(
+            "Queueing fcU or newPayload for {} syncing nodes",
+            syncing_nodes.len()
+        );
+        let method = req.method.name();
+        let circuit_config = self.circuit_config();
+        syncing_nodes
+            .iter()
+            .filter(|node| !node.circuit.is_open(&circuit_config) && self.supports(node, &method))
+            .for_each(|node| node.enqueue(req));
+        drop(syncing_nodes);
+
+        // majority is checked and either VALID or SYNCING
+        Ok(majority)
+    }
+
+    // sends getPayloadBodies to the primary node, then asks the other alive nodes for any bodies it returned as null
+    // (pruned or not synced yet) and merges them into one response
+    async fn get_payload_bodies(&self, request: &RpcRequest, jwt_token: String) -> (String, u16) {
+        let primary_node = match self.get_execution_node_for(&request.method).await {
+            Some(primary_node) => primary_node,
+            None => {
+                tracing::warn!("No primary node available");
+                return (
+                    make_error(&request.id, SERVER_ERROR, "No nodes available"),
+                    500,
+                );
+            }
+        };
+
+        let (resp_body, status) = match primary_node
+            .do_request_no_timeout(request, jwt_token.clone())
+            .await
+        {
+            Ok(resp) => resp,
+            Err(e) => {
+                tracing::warn!("Error from primary node: {}", e);
+                return (make_error(&request.id, INTERNAL_ERROR, &e.to_string()), 200);
+            }
+        };
+
+        let mut bodies = match parse_result(&resp_body)
+            .ok()
+            .and_then(|result| serde_json::from_value::<Vec<serde_json::Value>>(result).ok())
+        {
+            Some(bodies) => bodies,
+            None => return (resp_body, status), // let the CL handle whatever the primary node said
+        };
+
+        // ByRange responses are truncated if the node doesn't know about the end of the range yet
+        let primary_len = bodies.len();
+        if let Some(expected_len) = payload_bodies::expected_len(request) {
+            if bodies.len() < expected_len {
+                bodies.resize(expected_len, serde_json::Value::Null);
+            }
+        }
+
+        let missing = payload_bodies::missing_indices(&bodies);
+        if missing.is_empty() {
+            return (resp_body, status);
+        }
+
+        let (sub_request, positions) =
+            match payload_bodies::missing_bodies_request(request, &missing) {
+                Some(sub_request) => sub_request,
+                None => return (resp_body, status),
+            };
+
+        // only nodes that support the method and aren't failing engine calls are asked
+        let method = request.method.name();
+        let circuit_config = self.circuit_config();
+        let other_nodes = self
+            .alive_nodes
+            .read()
+            .await
+            .iter()
+            .filter(|node| {
+                node.url != primary_node.url
+                    && !node.circuit.is_open(&circuit_config)
+                    && self.supports(node, &method)
+            })
+            .cloned()
+            .collect::<Vec<Arc<Node>>>();
+
+        tracing::debug!(
+            "Primary node is missing {} payload bodies, asking {} other nodes",
+            missing.len(),
+            other_nodes.len()
+        );
+
+        let completed = join_all(
+            other_nodes
+                .iter()
+                .map(|node| node.do_request(&sub_request, jwt_token.clone())),
+        )
+        .await;
+
+        let mut filled = 0;
+        for (node, resp) in other_nodes.iter().zip(completed) {
+            let fill = match resp
+                .ok()
+                .and_then(|resp| parse_result(&resp.0).ok())
+                .and_then(|result| serde_json::from_value::<Vec<serde_json::Value>>(result).ok())
+            {
+                Some(fill) => fill,
+                None => {
+                    tracing::warn!("Couldn't get missing payload bodies from {}", node.url);
+                    continue;
+                }
+            };
+
+            filled += payload_bodies::fill_bodies(&mut bodies, &positions, fill);
+        }
+
+        tracing::debug!(
+            "Filled {} of {} missing payload bodies from other nodes",
+            filled,
+            missing.len()
+        );
+
+        // don't add trailing nulls the primary node didn't return
+        let last_body = bodies
+            .iter()
+            .rposition(|body| !body.is_null())
+            .map_or(0, |i| i + 1);
+        bodies.truncate(primary_len.max(last_body));
+
+        (make_response(&request.id, json!(bodies)), 200)
+    }
+
+    // sends the request to the primary node and nothing else
+    async fn primary_request(&self, request: &RpcRequest, jwt_token: String) -> (String, u16) {
+        let primary_node = match self.get_execution_node_for(&request.method).await {
+            Some(primary_node) => primary_node,
+            None => {
+                tracing::warn!("No primary node available");
+                return (
+                    make_error(&request.id, SERVER_ERROR, "No nodes available"),
+                    500,
+                );
+            }
+        };
+
+        match primary_node.do_request_no_timeout(request, jwt_token).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                tracing::warn!("Error from primary node: {}", e);
+                (make_error(&request.id, INTERNAL_ERROR, &e.to_string()), 200)
+            }
+        }
+    }
+
+    // asks every alive node for the blobs, since one node's mempool often misses blobs another has
+    async fn get_blobs(&self, request: &RpcRequest, jwt_token: String) -> (String, u16) {
+        let requested = match blobs::requested_len(request) {
+            Some(requested) => requested,
+            None => {
+                return (
+                    make_error(
+                        &request.id,
+                        INVALID_PARAMS,
+                        "Expected a list of versioned hashes",
+                    ),
+                    200,
+                )
+            }
+        };
+
+        let resps: Responses<(Arc<Node>, Option<Vec<serde_json::Value>>)> = self
+            .concurrent_node_requests(request, jwt_token, false)
+            .await;
+        if let Some(error) = resps.agreed_error {
+            return (make_el_error(&request.id, &error), 200);
+        }
+
+        let mut blob_hits = self.blob_hits.lock().unwrap();
+        for (node, resp) in resps.results.iter() {
+            blob_hits
+                .entry(node.url.clone())
+                .or_default()
+                .record(requested, blobs::found(resp.as_deref()));
+        }
+        drop(blob_hits);
+
+        let responses: Vec<Option<Vec<serde_json::Value>>> =
+            resps.results.into_iter().map(|(_, resp)| resp).collect();
+        let merged = blobs::merge_blobs(&request.method, requested, &responses);
+        tracing::debug!(
+            "{:?}: {} blobs requested, {} found by {} nodes",
+            request.method,
+            requested,
+            blobs::found(merged.as_array().map(Vec::as_slice)),
+            responses.len()
+        );
+
+        (make_response(&request.id, merged), 200)
+    }
+
+    async fn do_engine_route(
+        &self,
+        fork_config: &ForkConfig,
+        request: &RpcRequest,
+        jwt_token: String,
+    ) -> (String, u16) {
+        if matches!(
+            request.method,
+            EngineMethod::engine_newPayloadV1
+                | EngineMethod::engine_newPayloadV2
+                | EngineMethod::engine_newPayloadV3
+                | EngineMethod::engine_forkchoiceUpdatedV1
+                | EngineMethod::engine_forkchoiceUpdatedV2
+                | EngineMethod::engine_forkchoiceUpdatedV3
+        ) {
+            self.record_payload(request).await;
+        }
+
+        match request.method {
+            // getPayloadV1 is for getting a block to be proposed, so no use in getting from multiple nodes
+            EngineMethod::engine_getPayloadV1 => {
+                let node = match self.get_execution_node_for(&request.method).await {
+                    None => {
+                        return (
+                            make_error(&request.id, SERVER_ERROR, "No nodes available"),
+                            500,
+                        );
+                    }
+                    Some(node) => node,
+                };
+
+                let resp = node.do_request_no_timeout(request, jwt_token).await; // no timeout since the CL will just time us out themselves
+                tracing::debug!("engine_getPayloadV1 sent to node: {}", node.url);
+                match resp {
+                    Ok(resp) => (resp.0, resp.1),
+                    Err(e) => {
+                        tracing::warn!("engine_getPayloadV1 error: {}", e);
+
+                        if e.is_connect() || e.is_timeout() || e.is_request() {
+                            // if the error is a connection error, then we should set the node to syncing
+                            self.make_node_syncing(
+                                node.clone(),
+                                &format!("engine_getPayloadV1 failed: {}", e),
+                            )
+                            .await;
+                        }
+
+                        (make_error(&request.id, INTERNAL_ERROR, &e.to_string()), 200)
+                    }
+                }
+            } // getPayloadV1
+
+            EngineMethod::engine_getPayloadV2 => {
+                // getPayloadV2 has a different schema, where alongside the executionPayload it has a blockValue
+                // so we should send this to all the nodes and then return the one with the highest blockValue
+
+                // WILLNOTFIX the spec require getPayloadV2 to support getPayloadResponseV1, but it adds too much complexity
+                // for little benefit, as I doubt people actually use getPayloadResponseV2 with getPayloadV2
+                let resps: Responses<getPayloadResponseV2> =
+                    self.concurrent_requests(request, jwt_token, false).await;
+                if let Some(error) = resps.agreed_error {
+                    return (make_el_error(&request.id, &error), 200);
+                }
+                let resps = resps.results;
+                let most_profitable = resps
+                    .iter()
+                    .max_by(|resp_a, resp_b| resp_a.block_value.cmp(&resp_b.block_value));
+
+                if let Some(most_profitable_payload) = most_profitable {
+                    tracing::info!("Block {} requested by CL. All EL blocks profitability: {:?}. Using payload with value of {}", most_profitable_payload.execution_payload.block_number, resps.iter().map(|payload| payload.block_value).collect::<Vec<U256>>(), most_profitable_payload.block_value);
+                    return (
+                        make_response(&request.id, json!(most_profitable_payload)),
+                        200,
+                    );
+                }
+
+                // we have no payloads
+                tracing::warn!("No blocks found in EL engine_getPayloadV2 responses");
+                (
+                    make_error(
+                        &request.id,
+                        INTERNAL_ERROR,
+                        "No blocks found in EL engine_getPayloadV2 responses",
+                    ),
+                    200,
+                )
+            } // getPayloadV2
+
+            EngineMethod::engine_getPayloadV3 => {
+                // accepts only getPayloadResponseV3 since this version actually modifies the getPayload response (adding blob_bundle)
+                // as well as the nested execution payload
+
+                let resps: Responses<getPayloadResponseV3> =
+                    self.concurrent_requests(request, jwt_token, false).await;
+                if let Some(error) = resps.agreed_error {
+                    return (make_el_error(&request.id, &error), 200);
+                }
+                let resps = resps.results;
+                let most_profitable = resps
+                    .iter()
+                    .max_by(|resp_a, resp_b| resp_a.block_value.cmp(&resp_b.block_value));
+
+                // note: we may want to get the most profitable block from resps that have should_override_builder = true, note this in release
+
+                if let Some(most_profitable_payload) = most_profitable {
+                    tracing::info!("Block {} requested by CL. All EL blocks profitability: {:?}. Using payload with value of {}", most_profitable_payload.execution_payload.block_number, resps.iter().map(|payload| payload.block_value).collect::<Vec<U256>>(), most_profitable_payload.block_value);
+                    return (
+                        make_response(&request.id, json!(most_profitable_payload)),
+                        200,
+                    );
+                }
+
+                // we have no payloads
+                tracing::warn!("No blocks found in EL engine_getPayloadV3 responses");
+                (
+                    make_error(
+                        &request.id,
+                        INTERNAL_ERROR,
+                        "No blocks found in EL engine_getPayloadV2 responses",
+                    ),
+                    200,
+                )
+            } // getPayloadV3
+
+            EngineMethod::engine_newPayloadV1 | EngineMethod::engine_newPayloadV2 => {
+                tracing::debug!("Sending newPayloadV1|V2 to alive nodes");
+                let resps: Responses<(Arc<Node>, PayloadStatusV1)> =
+                    self.concurrent_votes(request, jwt_token.clone()).await;
+                if let Some(error) = resps.agreed_error {
+                    return (make_el_error(&request.id, &error), 200);
+                }
+                let resps = resps.results;
+
+                let resp = match self.fcu_logic(&resps, request).await {
+                    Ok(resp) => resp,
+                    Err(e) => match e {
+                        FcuLogicError::NoResponses => {
+                            tracing::error!(
+                                "No responses for {:?}, returning SYNCING",
+                                request.method
+                            );
+                            return (
+                                make_syncing_str(
+                                    &request.id,
+                                    &request.params[0],
+                                    &request.method,
+                                    None,
+                                ),
+                                200,
+                            );
+                        }
+                        FcuLogicError::NoMajority => {
+                            tracing::error!(
+                                "No majority for {:?}, returning SYNCING",
+                                request.method
+                            );
+                            return (
+                                make_syncing_str(
+                                    &request.id,
+                                    &request.params[0],
+                                    &request.method,
+                                    None,
+                                ),
+                                200,
+                            );
+                        }
+                        FcuLogicError::OneNodeIsInvalid => {
+                            tracing::error!(
+                                "One node is invalid for {:?}, returning SYNCING",
+                                request.method
+                            );
+                            return (
+                                make_syncing_str(
+                                    &request.id,
+                                    &request.params[0],
+                                    &request.method,
+                                    None,
+                                ),
+                                200,
+                            );
+                        }
+                    },
+                };
+
+                // we have a majority
+                (make_response(&request.id, json!(resp)), 200)
+            } // newPayloadV1, V2
+
+            EngineMethod::engine_newPayloadV3 => {
+                let newpayload_request = match newpayload_serializer(request.clone(), fork_config) {
+                    Ok(newpayload_request) => newpayload_request,
+                    Err(e) => {
+                        tracing::error!("Failed to serialize newPayloadV3: {}", e);
+                        return (make_error(&request.id, INVALID_PARAMS, &e.to_string()), 200);
+                    }
+                };
+
+                tracing::debug!("Sending newPayloadV3 to alive nodes");
+                let resps: Responses<(Arc<Node>, PayloadStatusV1)> =
+                    self.concurrent_votes(request, jwt_token.clone()).await;
+                if let Some(error) = resps.agreed_error {
+                    return (make_el_error(&request.id, &error), 200);
+                }
+                let resps = resps.results;
+
+                let resp = match self.fcu_logic(&resps, request).await {
+                    Ok(resp) => resp,
+                    Err(e) => match e {
+                        FcuLogicError::NoResponses => {
+                            tracing::error!(
+                                "No responses for {:?}, returning SYNCING",
+                                request.method
+                            );
+                            return (
+                                make_syncing_str(
+                                    &request.id,
+                                    &request.params[0],
+                                    &request.method,
+                                    newpayload_request.parent_beacon_block_root,
+                                ),
+                                200,
+                            );
+                        }
+                        FcuLogicError::NoMajority => {
+                            tracing::error!(
+                                "No majority for {:?}, returning SYNCING",
+                                request.method
+                            );
+                            return (
+                                make_syncing_str(
+                                    &request.id,
+                                    &request.params[0],
+                                    &request.method,
+                                    newpayload_request.parent_beacon_block_root,
+                                ),
+                                200,
+                            );
+                        }
+                        FcuLogicError::OneNodeIsInvalid => {
+                            tracing::error!(
+                                "One node is invalid for {:?}, returning SYNCING",
+                                request.method
+                            );
+                            return (
+                                make_syncing_str(
+                                    &request.id,
+                                    &request.params[0],
+                                    &request.method,
+                                    newpayload_request.parent_beacon_block_root,
+                                ),
+                                200,
+                            );
+                        }
+                    },
+                };
+
+                // we have a majority
+                (make_response(&request.id, json!(resp)), 200)
+            } // newPayloadV3
+
+            EngineMethod::engine_forkchoiceUpdatedV1
+            | EngineMethod::engine_forkchoiceUpdatedV2
+            | EngineMethod::engine_forkchoiceUpdatedV3 => {
+                tracing::debug!("Sending fcU to alive nodes");
+                let resps: Responses<(Arc<Node>, forkchoiceUpdatedResponse)> =
+                    self.concurrent_votes(request, jwt_token.clone()).await;
+                if let Some(error) = resps.agreed_error {
+                    return (make_el_error(&request.id, &error), 200);
+                }
+                let resps = resps.results;
+
+                let mut payloadstatus_resps =
+                    Vec::<(Arc<Node>, PayloadStatusV1)>::with_capacity(resps.len()); // faster to allocate in one go
+                let mut payload_id: Option<String> = None;
+
+                for (node, resp) in resps {
+                    if let Some(inner_payload_id) = resp.payloadId {
+                        // todo: make this look cleaner.
+                        payload_id = Some(inner_payload_id); // if payloadId is not null, then use that. all resps will have the same payloadId
+                    };
+                    payloadstatus_resps.push((node, resp.payloadStatus));
+                }
+
+                let resp = match self.fcu_logic(&payloadstatus_resps, request).await {
+                    Ok(resp) => resp,
+                    Err(e) => match e {
+                        FcuLogicError::NoResponses => {
+                            tracing::error!(
+                                "No responses for {:?}, returning SYNCING",
+                                request.method
+                            );
+                            return (
+                                make_syncing_str(
+                                    &request.id,
+                                    &request.params[0],
+                                    &request.method,
+                                    None,
+                                ),
+                                200,
+                            );
+                        }
+                        FcuLogicError::NoMajority => {
+                            tracing::error!(
+                                "No majority for {:?}, returning SYNCING",
+                                request.method
+                            );
+                            return (
+                                make_syncing_str(
+                                    &request.id,
+                                    &request.params[0],
+                                    &request.method,
+                                    None,
+                                ),
+                                200,
+                            );
+                        }
+                        FcuLogicError::OneNodeIsInvalid => {
+                            tracing::error!(
+                                "One node is invalid for {:?}, returning SYNCING",
+                                request.method
+                            );
+                            return (
+                                make_syncing_str(
+                                    &request.id,
+                                    &request.params[0],
+                                    &request.method,
+                                    None,
+                                ),
+                                200,
+                            );
+                        }
+                    },
+                };
+
+                // we have a majority
+                (
+                    make_response(
+                        &request.id,
+                        json!(forkchoiceUpdatedResponse {
+                            payloadStatus: resp,
+                            payloadId: payload_id,
+                        }),
+                    ),
+                    200,
+                )
+            } // fcU V1, V2
+
+            EngineMethod::engine_getPayloadBodiesByHashV1
+            | EngineMethod::engine_getPayloadBodiesByRangeV1 => {
+                self.get_payload_bodies(request, jwt_token).await
+            } // getPayloadBodiesByHashV1, ByRangeV1
+
+            EngineMethod::engine_getBlobsV1 | EngineMethod::engine_getBlobsV2 => {
+                self.get_blobs(request, jwt_token).await
+            } // getBlobsV1, V2
+
+            EngineMethod::engine_exchangeCapabilities => {
+                // syncing nodes are asked too, so they're only sent methods they support once they're synced
+                let resps: Responses<(Arc<Node>, Vec<String>)> = self
+                    .concurrent_node_requests(request, jwt_token, true)
+                    .await;
+                if let Some(error) = resps.agreed_error {
+                    return (make_el_error(&request.id, &error), 200);
+                }
+                if resps.results.is_empty() {
+                    return (
+                        make_error(&request.id, SERVER_ERROR, "No nodes available"),
+                        500,
+                    );
+                }
+
+                let mut known = self.capabilities.write().unwrap();
+                for (node, methods) in resps.results.iter() {
+                    known.insert(node.url.clone(), methods.clone());
+                }
+                drop(known);
+
+                let node_capabilities: Vec<Vec<String>> = resps
+                    .results
+                    .into_iter()
+                    .map(|(_, methods)| methods)
+                    .collect();
+                let combined =
+                    capabilities::combine(&node_capabilities, self.settings().capabilities_mode);
+                (make_response(&request.id, json!(combined)), 200)
+            } // exchangeCapabilities
+
+            EngineMethod::engine_getClientVersionV1 => {
+                // syncing nodes are asked too, EB can always answer for itself so node errors aren't passed on
+                let resps: Responses<Vec<ClientVersionV1>> =
+                    self.concurrent_requests(request, jwt_token, true).await;
+                (
+                    make_response(
+                        &request.id,
+                        json!(client_versions::client_versions(resps.results)),
+                    ),
+                    200,
+                )
+            } // getClientVersionV1
+
+            EngineMethod::Unknown(ref method)
+                if self.method_strategy(method) == config::MethodStrategy::Primary =>
+            {
+                tracing::debug!("Sending {} to the primary node only", method);
+                self.primary_request(request, jwt_token).await
+            } // engine methods EB doesn't know, broadcast-and-primary ones fall through
+
+            _ => {
+                // wait for primary node's response, but also send to all other nodes
+                let primary_node = match self.get_execution_node_for(&request.method).await {
+                    Some(primary_node) => primary_node,
+                    None => {
+                        tracing::warn!("No primary node available");
+                        return (
+                            make_error(&request.id, SERVER_ERROR, "No nodes available"),
+                            500,
+                        );
+                    }
+                };
+
+                let resp = primary_node.do_request_no_timeout(request, jwt_token).await;
+
+                // queue the request for the other nodes to replicate it
+                let circuit_config = self.circuit_config();
+                self.alive_nodes
+                    .read()
+                    .await
+                    .iter()
+                    .filter(|node| {
+                        node.url != primary_node.url
+                            && !node.circuit.is_open(&circuit_config)
+                            && self.supports(node, &request.method.name())
+                    })
+                    .for_each(|node| node.enqueue(request));
+
+                // return resp from primary node
+                match resp {
+                    Ok(resp) => (resp.0, resp.1),
+                    Err(e) => {
+                        tracing::warn!("Error from primary node: {}", e);
+                        (make_error(&request.id, INTERNAL_ERROR, &e.to_string()), 200)
+                    }
+                }
+            } // all other engine requests
+        }
+    }
+
+    async fn do_route_normal(&self, request: String, jwt_token: String) -> (String, u16) {
+        // simply send request to primary node
+        let primary_node = match self.get_execution_node().await {
+            Some(primary_node) => primary_node,
+            None => {
+                tracing::warn!("No primary node available for normal request");
+                return (
+                    make_error(&request_id(&request), SERVER_ERROR, "No nodes available"),
+                    500,
+                );
+            }
+        };
+
+        let id = request_id(&request);
+        let resp = primary_node
+            .do_request_no_timeout_str(request, jwt_token)
+            .await;
+        match resp {
+            Ok(resp) => (resp.0, resp.1),
+            Err(e) => (make_error(&id, INTERNAL_ERROR, &e.to_string()), 200),
+        }
+    }
+}
+
+// func to take body and headers from a request and return a string
+async fn route_all(
+    headers: HeaderMap,
+    Extension(router): Extension<Arc<NodeRouter>>,
+    body: String,
+) -> impl IntoResponse {
+    route_body(&headers, &router, body).await
+}
+
+async fn route_body(
+    headers: &HeaderMap,
+    router: &Arc<NodeRouter>,
+    body: String,
+) -> Response<String> {
+    if body.trim_start().starts_with('[') {
+        route_batch(headers, router, &body).await
+    } else {
+        route_request(headers, router, body).await
+    }
+}
+
+// a batch is split up and each request is routed on its own, in order, since engine calls can depend on the ones before them
+async fn route_batch(
+    headers: &HeaderMap,
+    router: &Arc<NodeRouter>,
+    body: &str,
+) -> Response<String> {
+    let requests: Vec<serde_json::Value> = match serde_json::from_str(body) {
+        Ok(requests) => requests,
+        Err(e) => {
+            tracing::error!(
+                "Couldn't deserialize batch request. Error: {}. Body: {}",
+                e,
+                body
+            );
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(make_error(
+                    &serde_json::Value::Null,
+                    PARSE_ERROR,
+                    "Couldn't deserialize request body",
+                ))
+                .unwrap();
+        }
+    };
+
+    if requests.is_empty() {
+        return Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(make_error(
+                &serde_json::Value::Null,
+                INVALID_REQUEST,
+                "Empty batch",
+            ))
+            .unwrap();
+    }
+
+    tracing::debug!("Batch of {} requests received", requests.len());
+    let mut resps = Vec::with_capacity(requests.len());
+    for request in requests {
+        let resp = route_request(headers, router, request.to_string())
+            .await
+            .into_body();
+
+        // notifications are routed like any other request, but aren't answered
+        if is_notification(&request) {
+            continue;
+        }
+
+        // some failures aren't json-rpc responses, wrap them so the batch stays valid
+        let resp = serde_json::from_str(&resp).unwrap_or_else(|_| {
+            json!({"jsonrpc": "2.0", "id": request.get("id"), "error": {"code": INTERNAL_ERROR, "message": resp}})
+        });
+        resps.push(resp);
+    }
+
+    // a batch of only notifications gets no body at all
+    if resps.is_empty() {
+        return Response::builder()
+            .status(StatusCode::NO_CONTENT)
+            .body(String::new())
+            .unwrap();
+    }
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(serde_json::Value::Array(resps).to_string())
+        .unwrap()
+}
+
+// a request without an id, the client doesn't want a response
+fn is_notification(request: &serde_json::Value) -> bool {
+    request
+        .as_object()
+        .is_some_and(|request| !request.contains_key("id"))
+}
+
+async fn route_request(
+    headers: &HeaderMap,
+    router: &Arc<NodeRouter>,
+    body: String,
+) -> Response<String> {
+    let j: serde_json::Value = match serde_json::from_str(&body) {
+        Ok(j) => j,
+        Err(e) => {
+            tracing::error!("Couldn't deserialize request. Error: {}. Body: {}", e, body);
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(make_error(
+                    &serde_json::Value::Null,
+                    PARSE_ERROR,
+                    "Couldn't deserialize request body",
+                ))
+                .unwrap();
+        }
+    };
+
+    let meth = match j["method"].as_str() {
+        Some(meth) => meth,
+        None => {
+            tracing::error!("Request has no method field");
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(make_error(
+                    &j["id"],
+                    INVALID_REQUEST,
+                    "Request has no method field",
+                ))
+                .unwrap();
+        }
+    };
+
+    tracing::debug!("Request received, method: {}", j["method"]);
+
+    if meth.starts_with("engine_") {
+        tracing::trace!("Routing {} to engine route", j["method"]);
+
+        let request: RpcRequest = match serde_json::from_str(&body) {
+            Ok(request) => request,
+            Err(e) => {
+                tracing::error!("Error deserializing {} request: {}", j["method"], e);
+                return Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(make_error(
+                        &j["id"],
+                        INVALID_REQUEST,
+                        "Error deserializing request",
+                    ))
+                    .unwrap();
+            }
+        };
+
+        let jwt_token = match headers.get("Authorization") {
+            Some(jwt_token) => match jwt_token.to_str() {
+                Ok(jwt_token) => jwt_token,
+                Err(e) => {
+                    tracing::error!("Error while converting jwt token to string: {}", e);
+                    return Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .header(header::CONTENT_TYPE, "application/json")
+                        .body(make_error(
+                            &j["id"],
+                            INVALID_REQUEST,
+                            "Error while converting jwt token to string",
+                        ))
+                        .unwrap();
+                }
+            },
+            None => {
+                tracing::error!("Request has no Authorization header");
+                return Response::builder()
+                    .status(StatusCode::BAD_REQUEST)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(make_error(
+                        &j["id"],
+                        INVALID_REQUEST,
+                        "Request has no Authorization header",
+                    ))
+                    .unwrap();
+            }
+        };
+
+        let (resp, status) = router
+            .do_engine_route(&router.fork_config, &request, jwt_token.to_string())
+            .await;
+
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(resp)
+            .unwrap()
+    }
+    // engine requests
+    else {
+        tracing::trace!("Routing to normal route");
+
+        let jwt_token = headers.get("Authorization");
+        if jwt_token.is_none() {
+            let (resp, status) = router
+                .do_route_normal(
+                    body,
+                    format!(
+                        "Bearer {}",
+                        router.primary_node.read().await.make_jwt().unwrap()
+                    ), // supporting requests without jwt tokens to authrpc is used for OE.
+                ) // open an issue if you need this to be changed
+                .await;
+
+            return Response::builder()
+                .status(status)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(resp)
+                .unwrap();
+        }
+
+        let jwt_token = match headers.get("Authorization") {
+            Some(header_value) => match header_value.to_str() {
+                Ok(jwt_str) => jwt_str.to_string(),
+                Err(e) => {
+                    tracing::warn!(
+                        "Could not extract authorization header from normal request: {}",
+                        e
+                    );
+                    return Response::builder()
+                        .status(400)
+                        .header(header::CONTENT_TYPE, "application/json")
+                        .body(r#"{"error": "Could not extract authorization header from normal request}"#.to_string())
+                        .unwrap();
+                }
+            },
+            None => {
+                // should never happen, should've been caught before and been replaced
+                return Response::builder()
+                    .status(400)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(
+                        r#"{"error": "This should never happen, please open an issue.}"#
+                            .to_string(),
+                    )
+                    .unwrap();
+            }
+        };
+
+        let (resp, status) = router.do_route_normal(body, jwt_token).await;
+
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(resp)
+            .unwrap()
+    } // all other non-engine requests
+}
+
+async fn make_metrics_report(
+    router: Arc<NodeRouter>,
+) -> Result<serde_json::Value, serde_json::Error> {
+    let syncing_nodes = router.alive_but_syncing_nodes.read().await;
+    let alive_nodes = router.alive_nodes.read().await;
+    let mut both = syncing_nodes.clone();
+    both.append(&mut alive_nodes.clone());
+    drop(alive_nodes);
+    drop(syncing_nodes);
+
+    let mut futs = Vec::new();
+    both.iter().for_each(|node| {
+        futs.push(async move { (node.url.clone(), node.status.read().await.clone()) })
+    });
+
+    let statuses: HashMap<String, NodeHealth> = join_all(futs).await.into_iter().collect();
+    let resp_times = statuses
+        .iter()
+        .map(|(url, status)| (url.clone(), status.resp_time))
+        .collect();
+    let heads = statuses
+        .iter()
+        .filter_map(|(url, status)| status.head.map(|head| (url.clone(), head)))
+        .collect();
+    let (lagging_nodes, syncing_nodes): (Vec<String>, Vec<String>) = router
+        .alive_but_syncing_nodes
+        .read()
+        .await
+        .iter()
+        .map(|node| node.url.clone())
+        .partition(|url| {
+            statuses
+                .get(url)
+                .is_some_and(|status| status.status == SyncingStatus::Lagging)
+        });
+
+    let nodes = router.nodes.lock().await.clone();
+    let queue_depths = nodes
+        .iter()
+        .map(|node| (node.url.clone(), node.queue.depth()))
+        .collect();
+    let queue_drops = nodes
+        .iter()
+        .map(|node| (node.url.clone(), node.queue.dropped()))
+        .collect();
+    let circuit_states = nodes
+        .iter()
+        .map(|node| (node.url.clone(), node.circuit.state()))
+        .collect();
+    let labels = nodes
+        .iter()
+        .filter_map(|node| Some((node.url.clone(), node.label.clone()?)))
+        .collect();
+    let blob_hits = {
+        let blob_hits = router.blob_hits.lock().unwrap();
+        nodes
+            .iter()
+            .filter_map(|node| Some((node.url.clone(), *blob_hits.get(&node.url)?)))
+            .collect()
+    };
+    let versions = client_versions::node_versions(&router).await;
+    let client_version_warnings = client_versions::mismatches(&versions);
+    let mut node_states = HashMap::with_capacity(nodes.len());
+    for node in nodes.iter() {
+        node_states.insert(node.url.clone(), node.status.read().await.status);
+    }
+
+    let metrics_report = MetricsReport {
+        response_times: resp_times,
+        alive_nodes: router
+            .alive_nodes
+            .read()
+            .await
+            .iter()
+            .map(|node| node.url.clone())
+            .collect(),
+        syncing_nodes,
+        lagging_nodes,
+        dead_nodes: router
+            .dead_nodes
+            .read()
+            .await
+            .iter()
+            .map(|node| node.url.clone())
+            .collect(),
+        primary_node: router.primary_node.read().await.url.clone(),
+        node_states,
+        labels,
+        heads,
+        queue_depths,
+        queue_drops,
+        circuit_states,
+        blob_hits,
+        client_versions: versions.into_iter().collect(),
+        client_version_warnings,
+    };
+
+    serde_json::to_value(metrics_report)
+}
+
+async fn metrics(Extension(router): Extension<Arc<NodeRouter>>) -> impl IntoResponse {
+    let report = match make_metrics_report(router).await {
+        Ok(report) => report,
+        Err(e) => {
+            tracing::error!("Could not make metrics report: {}", e);
+            json!({"error": format!("Could not make metrics report: {}", e)})
+        }
+    };
+
+    let resp_body = match serde_json::to_string(&report) {
+        Ok(resp_body) => resp_body,
+        Err(e) => {
+            tracing::error!("Unable to serialize metrics report: {}", e);
+            r#"{"error":"Unable to serialize metrics report"}"#.to_string()
+        }
+    };
+
+    Response::builder()
+        .status(200)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(resp_body)
+        .unwrap()
+}
+
+// every node's recent state transitions, oldest first
+async fn events(Extension(router): Extension<Arc<NodeRouter>>) -> impl IntoResponse {
+    let nodes = router.nodes.lock().await.clone();
+    let mut transitions = Vec::new();
+    for node in nodes.iter() {
+        transitions.extend(node.transitions.read().await.iter().cloned());
+    }
+    transitions.sort_by_key(|transition| transition.timestamp);
+
+    let resp_body = match serde_json::to_string(&transitions) {
+        Ok(resp_body) => resp_body,
+        Err(e) => {
+            tracing::error!("Unable to serialize events: {}", e);
+            r#"{"error":"Unable to serialize events"}"#.to_string()
+        }
+    };
+
+    Response::builder()
+        .status(200)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(resp_body)
+        .unwrap()
+}
+
+async fn disagreements_handler(Extension(router): Extension<Arc<NodeRouter>>) -> impl IntoResponse {
+    let disagreements = router.disagreements();
+
+    Response::builder()
+        .status(200)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(json!(disagreements).to_string())
+        .unwrap()
+}
+
+// calls router.recheck, returns recheck time, and metrics
+async fn recheck(router: Arc<NodeRouter>) -> Result<(String, StatusCode), String> {
+    let start = std::time::Instant::now();
+    router.recheck().await;
+    let resp_time = start.elapsed().as_micros();
+
+    let mut report = match make_metrics_report(router).await {
+        Ok(report) => report,
+        Err(e) => {
+            tracing::error!("Unable to get metrics report: {}", e);
+            return Err(
+                r#"{"error":"Unable to get metrics report; Recheck succeeded."}"#.to_string(),
+            );
+        }
+    };
+
+    report["recheck_time"] = serde_json::to_value(resp_time).unwrap();
+
+    let resp_body = match serde_json::to_string(&report) {
+        Ok(resp_body) => resp_body,
+        Err(e) => {
+            tracing::error!("Unable to serialize metrics report: {}", e);
+            return Err(r#"{"error":"Unable to serialize metrics report"}"#.to_string());
+        }
+    };
+
+    Ok((resp_body, StatusCode::OK))
+}
+
+async fn recheck_handler(Extension(router): Extension<Arc<NodeRouter>>) -> impl IntoResponse {
+    match recheck(router).await {
+        Ok((resp_body, status_code)) => Response::builder()
+            .status(status_code)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(resp_body)
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(500)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(e)
+            .unwrap(),
+    }
+}
+
+async fn add_node(
+    Extension(router): Extension<Arc<NodeRouter>>,
+    extract::Json(request): extract::Json<NodeList>,
+) -> impl IntoResponse {
+    let specs = request.nodes.clone();
+    let request_timeout = router.settings().request_timeout;
+    let mut nodes = match request.create_new_nodes(router.general_jwt(), request_timeout) {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            tracing::error!("Unable to create nodes from NodeList: {}", e);
+            return Response::builder()
+                .status(500)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(format!(
+                    r#"{{"error":"Unable to get nodes from NodeList: {}"}}"#,
+                    e
+                ))
+                .unwrap();
+        }
+    };
+
+    // a node on another chain fails the whole request, nodes we just couldn't ask are checked again on recheck
+    let chain_checks = join_all(nodes.iter().map(|node| {
+        node.verify_chain(router.fork_config.chain_id, router.fork_config.genesis_hash)
+    }))
+    .await;
+    for result in chain_checks {
+        if let Err(ChainCheckError::WrongChain(reason)) = result {
+            tracing::error!("Refusing to add node: {}", reason);
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(json!({ "error": reason }).to_string())
+                .unwrap();
+        }
+    }
+
+    tracing::info!("Adding {} new nodes", nodes.len());
+    router.nodes.lock().await.append(&mut nodes);
+
+    if let Some(node_store) = &router.node_store {
+        if let Err(e) = node_store.record_added(&specs).await {
+            tracing::error!("Unable to save added nodes: {}", e);
+        }
+    }
+
+    match recheck(router).await {
+        Ok((resp_body, status_code)) => Response::builder()
+            .status(status_code)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(resp_body)
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(500)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(e)
+            .unwrap(),
+    }
+}
+
+async fn node_admin_response(
+    router: Arc<NodeRouter>,
+    result: Result<Arc<Node>, NodeAdminError>,
+) -> Response<axum::body::Body> {
+    if let Err(e) = result {
+        let status = match e {
+            NodeAdminError::NotFound => StatusCode::NOT_FOUND,
+            NodeAdminError::NotDisabled | NodeAdminError::LastNode => StatusCode::BAD_REQUEST,
+        };
+        return Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(json!({ "error": e.to_string() }).to_string().into())
+            .unwrap();
+    }
+
+    match recheck(router).await {
+        Ok((resp_body, status_code)) => Response::builder()
+            .status(status_code)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(resp_body.into())
+            .unwrap(),
+        Err(e) => Response::builder()
+            .status(500)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(e.into())
+            .unwrap(),
+    }
+}
+
+async fn disable_node(
+    Extension(router): Extension<Arc<NodeRouter>>,
+    extract::Json(request): extract::Json<NodeSelector>,
+) -> impl IntoResponse {
+    let result = router.disable_node(&request.node).await;
+    node_admin_response(router, result).await
+}
+
+async fn enable_node(
+    Extension(router): Extension<Arc<NodeRouter>>,
+    extract::Json(request): extract::Json<NodeSelector>,
+) -> impl IntoResponse {
+    let result = router.enable_node(&request.node).await;
+    node_admin_response(router, result).await
+}
+
+async fn remove_node(
+    Extension(router): Extension<Arc<NodeRouter>>,
+    extract::Json(request): extract::Json<NodeSelector>,
+) -> impl IntoResponse {
+    let result = router.remove_node(&request.node).await;
+    node_admin_response(router, result).await
+}
+
+fn upgrade_response(
+    result: Result<upgrade::UpgradeProgress, upgrade::UpgradeError>,
+) -> Response<axum::body::Body> {
+    let (status, resp_body) = match result {
+        Ok(progress) => (StatusCode::OK, json!(progress)),
+        Err(e) => {
+            let status = match e {
+                upgrade::UpgradeError::NotFound => StatusCode::NOT_FOUND,
+                _ => StatusCode::BAD_REQUEST,
+            };
+            (status, json!({ "error": e.to_string() }))
+        }
+    };
+
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(resp_body.to_string().into())
+        .unwrap()
+}
+
+async fn start_upgrade(
+    Extension(router): Extension<Arc<NodeRouter>>,
+    extract::Json(request): extract::Json<NodeSelector>,
+) -> impl IntoResponse {
+    upgrade_response(upgrade::start_upgrade(router, &request.node).await)
+}
+
+async fn continue_upgrade(
+    Extension(router): Extension<Arc<NodeRouter>>,
+    extract::Json(request): extract::Json<NodeSelector>,
+) -> impl IntoResponse {
+    upgrade_response(upgrade::continue_upgrade(&router, &request.node).await)
+}
+
+async fn upgrades(Extension(router): Extension<Arc<NodeRouter>>) -> impl IntoResponse {
+    let upgrades = router
+        .upgrades
+        .read()
+        .await
+        .values()
+        .cloned()
+        .collect::<Vec<upgrade::UpgradeProgress>>();
+
+    Response::builder()
+        .status(200)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(json!(upgrades).to_string())
+        .unwrap()
+}
+
+async fn reload_handler(Extension(router): Extension<Arc<NodeRouter>>) -> impl IntoResponse {
+    let (status, resp_body) = match reload::reload(&router).await {
+        Ok(report) => (StatusCode::OK, json!(report)),
+        Err(e) => {
+            tracing::error!("Config reload failed, keeping the current config: {}", e);
+            (StatusCode::BAD_REQUEST, json!({ "error": e }))
+        }
+    };
+
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(resp_body.to_string())
+        .unwrap()
+}
+
+// the engine api the CL talks to
+pub fn engine_app(router: Arc<NodeRouter>) -> Router {
+    Router::new()
+        .route("/", axum::routing::post(route_all))
+        .layer(Extension(router))
+        .layer(DefaultBodyLimit::disable()) // no body limit since some requests can be quite large
+}
+
+// every admin route needs a token the router's admin auth accepts
+pub fn admin_app(router: Arc<NodeRouter>) -> Router {
+    Router::new()
+        .route("/events", axum::routing::get(events))
+        .route("/disagreements", axum::routing::get(disagreements_handler))
+        .route("/recheck", axum::routing::get(recheck_handler))
+        .route("/add_nodes", axum::routing::post(add_node))
+        .route("/disable_node", axum::routing::post(disable_node))
+        .route("/enable_node", axum::routing::post(enable_node))
+        .route("/remove_node", axum::routing::post(remove_node))
+        .route("/start_upgrade", axum::routing::post(start_upgrade))
+        .route("/continue_upgrade", axum::routing::post(continue_upgrade))
+        .route("/upgrades", axum::routing::get(upgrades))
+        .route("/reload", axum::routing::post(reload_handler))
+        .route_layer(axum::middleware::from_fn(admin::require_auth))
+        // scrapers read /metrics without a token, so it's added after the auth layer
+        .route("/metrics", axum::routing::get(metrics))
+        .layer(Extension(router))
+}
+
+// the health checks that pick the primary node, client version polling and reloading on SIGHUP if the router has a reloader
+pub fn spawn_background_tasks(router: &Arc<NodeRouter>) {
+    let router_clone = router.clone();
+    tracing::debug!("Starting background recheck task");
+    tokio::spawn(async move {
+        loop {
+            router_clone.recheck().await;
+            tokio::time::sleep(router_clone.settings().recheck_interval).await;
+        }
+    });
+
+    // keep track of which client versions the nodes are running
+    tokio::spawn(client_versions::poll_client_versions(router.clone()));
+
+    #[cfg(unix)]
+    if router.reloader.is_some() {
+        tokio::spawn(reload::reload_on_sighup(router.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syncing_str_invalid_block_hash() {
+        let payload = json!({
+            "parentHash": format!("0x{}", "00".repeat(32)),
+            "feeRecipient": format!("0x{}", "00".repeat(20)),
+            "stateRoot": format!("0x{}", "00".repeat(32)),
+            "receiptsRoot": format!("0x{}", "00".repeat(32)),
+            "logsBloom": format!("0x{}", "00".repeat(256)),
+            "prevRandao": format!("0x{}", "00".repeat(32)),
+            "blockNumber": "0x1",
+            "gasLimit": "0x1",
+            "gasUsed": "0x0",
+            "timestamp": "0x1",
+            "extraData": "0x",
+            "baseFeePerGas": "0x1",
+            "blockHash": format!("0x{}", "11".repeat(32)),
+            "transactions": []
+        });
+
+        let resp: serde_json::Value = serde_json::from_str(&make_syncing_str(
+            &json!(7),
+            &payload,
+            &EngineMethod::engine_newPayloadV1,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(resp["id"], 7);
+        assert_eq!(resp["result"]["status"], "INVALID_BLOCK_HASH");
+        assert!(resp.get("error").is_none());
+    }
+
+    #[test]
+    fn test_agreed_error() {
+        let unknown_payload = json!({"code": -38001, "message": "Unknown payload"});
+        let unsupported_fork = json!({"code": -38005, "message": "Unsupported fork"});
+
+        assert_eq!(agreed_error(&[]), None);
+        // the message can differ between clients, only the code has to match
+        assert_eq!(
+            agreed_error(&[
+                unknown_payload.clone(),
+                json!({"code": -38001, "message": "payload not found"})
+            ]),
+            Some(unknown_payload.clone())
+        );
+        assert_eq!(agreed_error(&[unknown_payload, unsupported_fork]), None);
+    }
+}
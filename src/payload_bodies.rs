@@ -0,0 +1,152 @@
+use serde_json::{json, Value};
+use types::*;
+
+// the most bodies the engine api lets the CL ask for in one request
+pub const MAX_BODIES: usize = 1024;
+
+// number of bodies the CL asked for, used to tell apart truncated responses from complete ones
+// capped at MAX_BODIES so a bogus count can't make us allocate a huge response
+pub fn expected_len(request: &RpcRequest) -> Option<usize> {
+    let expected_len = match request.method {
+        EngineMethod::engine_getPayloadBodiesByHashV1 => {
+            request.params.get(0)?.as_array().map(|hashes| hashes.len())
+        }
+        EngineMethod::engine_getPayloadBodiesByRangeV1 => {
+            let QuantityU64 { value: count } =
+                serde_json::from_value(request.params.get(1)?.clone()).ok()?;
+            usize::try_from(count).ok()
+        }
+        _ => None,
+    }?;
+    Some(expected_len.min(MAX_BODIES))
+}
+
+pub fn missing_indices(bodies: &[Value]) -> Vec<usize> {
+    bodies
+        .iter()
+        .enumerate()
+        .filter(|(_, body)| body.is_null())
+        .map(|(index, _)| index)
+        .collect()
+}
+
+// builds a request asking only for the bodies at `missing`
+// returns the request alongside the index in the original response that each entry of the new response fills
+pub fn missing_bodies_request(
+    request: &RpcRequest,
+    missing: &[usize],
+) -> Option<(RpcRequest, Vec<usize>)> {
+    let mut sub_request = request.clone();
+
+    match request.method {
+        EngineMethod::engine_getPayloadBodiesByHashV1 => {
+            let hashes = request.params.get(0)?.as_array()?;
+            let missing_hashes = missing
+                .iter()
+                .map(|index| hashes.get(*index).cloned())
+                .collect::<Option<Vec<Value>>>()?;
+
+            sub_request.params = json!([missing_hashes]);
+            Some((sub_request, missing.to_vec()))
+        }
+        EngineMethod::engine_getPayloadBodiesByRangeV1 => {
+            // ranges can't have holes, so ask for the smallest range covering every missing body
+            let QuantityU64 { value: start } =
+                serde_json::from_value(request.params.get(0)?.clone()).ok()?;
+            let first = *missing.first()?;
+            let last = *missing.last()?;
+
+            let sub_start = start.checked_add(first as u64)?;
+            let sub_count = (last - first + 1) as u64;
+            sub_request.params = json!([
+                QuantityU64 { value: sub_start },
+                QuantityU64 { value: sub_count }
+            ]);
+            Some((sub_request, (first..=last).collect()))
+        }
+        _ => None,
+    }
+}
+
+// fills the null bodies at `positions` with the matching entry from `fill`, returns how many were filled
+pub fn fill_bodies(bodies: &mut [Value], positions: &[usize], fill: Vec<Value>) -> usize {
+    let mut filled = 0;
+
+    for (position, body) in positions.iter().zip(fill) {
+        if body.is_null() {
+            continue;
+        }
+
+        if let Some(slot) = bodies.get_mut(*position) {
+            if slot.is_null() {
+                *slot = body;
+                filled += 1;
+            }
+        }
+    }
+
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_request(method: &str, params: Value) -> RpcRequest {
+        serde_json::from_value(
+            json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params}),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_fill_by_range() {
+        let request = make_request("engine_getPayloadBodiesByRangeV1", json!(["0x10", "0x5"]));
+        assert_eq!(expected_len(&request), Some(5));
+        let huge = make_request(
+            "engine_getPayloadBodiesByRangeV1",
+            json!(["0x10", "0xffffffffffffffff"]),
+        );
+        assert_eq!(expected_len(&huge), Some(MAX_BODIES));
+
+        // primary is missing blocks 0x11 and 0x13, and doesn't know about 0x14 yet
+        let mut bodies = vec![json!({"a": 0}), Value::Null, json!({"a": 2}), Value::Null];
+        bodies.resize(5, Value::Null);
+
+        let missing = missing_indices(&bodies);
+        assert_eq!(missing, vec![1, 3, 4]);
+
+        let (sub_request, positions) = missing_bodies_request(&request, &missing).unwrap();
+        assert_eq!(sub_request.params, json!(["0x11", "0x4"]));
+        assert_eq!(positions, vec![1, 2, 3, 4]);
+
+        // the other node doesn't have 0x14 either
+        let fill = vec![json!({"a": 1}), json!({"b": 2}), json!({"a": 3})];
+        assert_eq!(fill_bodies(&mut bodies, &positions, fill), 2);
+        assert_eq!(bodies[1], json!({"a": 1}));
+        assert_eq!(bodies[2], json!({"a": 2}));
+        assert_eq!(bodies[3], json!({"a": 3}));
+        assert!(bodies[4].is_null());
+    }
+
+    #[test]
+    fn test_fill_by_hash() {
+        let request = make_request(
+            "engine_getPayloadBodiesByHashV1",
+            json!([["0x01", "0x02", "0x03"]]),
+        );
+
+        let mut bodies = vec![Value::Null, json!({"a": 1}), Value::Null];
+        let missing = missing_indices(&bodies);
+
+        let (sub_request, positions) = missing_bodies_request(&request, &missing).unwrap();
+        assert_eq!(sub_request.params, json!([["0x01", "0x03"]]));
+
+        assert_eq!(
+            fill_bodies(&mut bodies, &positions, vec![Value::Null, json!({"a": 3})]),
+            1
+        );
+        assert!(bodies[0].is_null());
+        assert_eq!(bodies[2], json!({"a": 3}));
+    }
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::Mutex;
+use types::NodeConfig;
+
+// nodes added and removed at runtime, so they survive a restart
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct PersistedNodes {
+    // node urls with their options, the same way they're given to --nodes
+    pub added: Vec<String>,
+    // urls of nodes that were removed
+    pub removed: Vec<String>,
+}
+
+// the url of a node without the options appended to it
+pub fn node_url(node: &str) -> &str {
+    node.split('#').next().unwrap_or(node)
+}
+
+impl PersistedNodes {
+    // applies the added and removed nodes to the ones from the command line or config file
+    pub fn merge(&self, nodes: Vec<NodeConfig>) -> Result<Vec<NodeConfig>, String> {
+        let mut merged: Vec<NodeConfig> = nodes
+            .into_iter()
+            .filter(|node| !self.removed.contains(&node.url))
+            .collect();
+
+        for node in self.added.iter() {
+            let node = NodeConfig::from_spec(node)?;
+            if !merged.iter().any(|merged_node| merged_node.url == node.url) {
+                merged.push(node);
+            }
+        }
+
+        Ok(merged)
+    }
+
+    fn record_added(&mut self, node: &str) {
+        let url = node_url(node);
+        self.removed.retain(|removed_url| removed_url != url);
+        self.added.retain(|added_node| node_url(added_node) != url);
+        self.added.push(node.to_string());
+    }
+
+    fn record_removed(&mut self, url: &str) {
+        self.added.retain(|added_node| node_url(added_node) != url);
+        if !self.removed.iter().any(|removed_url| removed_url == url) {
+            self.removed.push(url.to_string());
+        }
+    }
+}
+
+pub struct NodeStore {
+    path: PathBuf,
+    nodes: Mutex<PersistedNodes>,
+}
+
+impl NodeStore {
+    // a missing file is the same as an empty one
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let nodes = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("Could not parse state file {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => PersistedNodes::default(),
+            Err(e) => {
+                return Err(format!(
+                    "Could not read state file {}: {}",
+                    path.display(),
+                    e
+                ))
+            }
+        };
+
+        Ok(NodeStore {
+            path,
+            nodes: Mutex::new(nodes),
+        })
+    }
+
+    pub async fn nodes(&self) -> PersistedNodes {
+        self.nodes.lock().await.clone()
+    }
+
+    pub async fn record_added(&self, nodes: &[String]) -> Result<(), String> {
+        let mut persisted = self.nodes.lock().await;
+        nodes.iter().for_each(|node| persisted.record_added(node));
+        self.save(&persisted)
+    }
+
+    pub async fn record_removed(&self, url: &str) -> Result<(), String> {
+        let mut persisted = self.nodes.lock().await;
+        persisted.record_removed(url);
+        self.save(&persisted)
+    }
+
+    // writes to a temporary file first so a crash can't leave a half written state file
+    fn save(&self, nodes: &PersistedNodes) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(nodes)
+            .map_err(|e| format!("Could not serialize state file: {}", e))?;
+
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, contents)
+            .and_then(|_| std::fs::rename(&tmp_path, &self.path))
+            .map_err(|e| format!("Could not write state file {}: {}", self.path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_persisted_nodes() {
+        let mut persisted = PersistedNodes::default();
+        persisted.record_added("http://c:8551#label=c");
+        persisted.record_removed("http://b:8551");
+
+        // re-adding a removed node forgets the removal, removing an added node forgets the addition
+        persisted.record_added("http://d:8551");
+        persisted.record_removed("http://d:8551");
+        persisted.record_removed("http://e:8551");
+        persisted.record_added("http://e:8551#jwt-secret=/e");
+
+        let nodes = [
+            "http://a:8551",
+            "http://b:8551#jwt-secret=/b",
+            "http://e:8551",
+        ]
+        .iter()
+        .map(|node| NodeConfig::from_spec(node).unwrap())
+        .collect();
+
+        let merged = persisted.merge(nodes).unwrap();
+        assert_eq!(
+            merged
+                .iter()
+                .map(|node| node.url.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["http://a:8551", "http://e:8551", "http://c:8551"]
+        );
+        assert_eq!(merged[2].label.as_deref(), Some("c"));
+    }
+}
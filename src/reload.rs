@@ -0,0 +1,333 @@
+use crate::{admin, config, NodeRouter, RouterSettings};
+use ethereum_types::H256;
+use serde::Serialize;
+use futures::future::join_all;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{sync::Mutex, time::Duration};
+use types::{node::Node, *};
+
+// re-reads the command line's config file, jwt secrets and admin token
+pub struct Reloader {
+    matches: clap::ArgMatches<'static>,
+    started_with: StartupSettings,
+    // urls of the nodes the config gave last time, nodes added at runtime aren't in it
+    // also makes sure there's one reload at a time
+    configured: Mutex<Vec<String>>,
+}
+
+// settings that only take effect on restart, as EB was started with
+struct StartupSettings {
+    listen_addr: String,
+    port: u16,
+    log_level: String,
+    state_file: Option<String>,
+    admin_addr: Option<SocketAddr>,
+    network: config::Network,
+    shanghai_fork_epoch: Option<u64>,
+    cancun_fork_epoch: Option<u64>,
+    chain_id: u64,
+    genesis_hash: H256,
+}
+
+impl StartupSettings {
+    fn new(settings: &config::Settings) -> Self {
+        StartupSettings {
+            listen_addr: settings.listen_addr.clone(),
+            port: settings.port,
+            log_level: settings.log_level.clone(),
+            state_file: settings.state_file.clone(),
+            admin_addr: settings.admin_addr,
+            network: settings.network,
+            shanghai_fork_epoch: settings.fork_config.shanghai_fork_epoch,
+            cancun_fork_epoch: settings.fork_config.cancun_fork_epoch,
+            chain_id: settings.fork_config.chain_id,
+            genesis_hash: settings.fork_config.genesis_hash,
+        }
+    }
+
+    // names of the settings that changed
+    fn changed(&self, other: &StartupSettings) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.listen_addr != other.listen_addr {
+            changed.push("listen-addr");
+        }
+        if self.port != other.port {
+            changed.push("port");
+        }
+        if self.log_level != other.log_level {
+            changed.push("log-level");
+        }
+        if self.state_file != other.state_file {
+            changed.push("state-file");
+        }
+        if self.admin_addr != other.admin_addr {
+            changed.push("admin-addr");
+        }
+        if self.network != other.network
+            || self.shanghai_fork_epoch != other.shanghai_fork_epoch
+            || self.cancun_fork_epoch != other.cancun_fork_epoch
+            || self.chain_id != other.chain_id
+            || self.genesis_hash != other.genesis_hash
+        {
+            changed.push("network");
+        }
+        changed
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ReloadReport {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    // nodes whose options changed, they keep their state and history
+    pub updated: Vec<String>,
+    // nodes added through /add_nodes that aren't in the config, kept because there's no state file to save them to
+    pub kept: Vec<String>,
+    // settings that changed but are only used after a restart
+    pub restart_required: Vec<&'static str>,
+}
+
+impl Reloader {
+    pub fn new(matches: clap::ArgMatches<'static>, settings: &config::Settings) -> Self {
+        Reloader {
+            matches,
+            started_with: StartupSettings::new(settings),
+            configured: Mutex::new(settings.nodes.iter().map(|node| node.url.clone()).collect()),
+        }
+    }
+}
+
+// whether the node has to be recreated to match its config
+fn node_changed(node: &Node, config: &NodeConfig, request_timeout: Duration) -> bool {
+    let timeout = config
+        .timeout
+        .map(Duration::from_millis)
+        .unwrap_or(request_timeout);
+    node.config != *config || node.timeout != timeout
+}
+
+// the keys nodes kept through a reload sign with from now on
+// nodes added at runtime without their own secret use the general one, so they have to follow it when it changes
+fn jwt_keys(
+    nodes: Vec<Arc<Node>>,
+    general_jwt: Option<&jsonwebtoken::EncodingKey>,
+) -> Result<Vec<(Arc<Node>, jsonwebtoken::EncodingKey)>, String> {
+    nodes
+        .into_iter()
+        .map(|node| {
+            let jwt_key = node.config.jwt_key(general_jwt)?;
+            Ok((node, jwt_key))
+        })
+        .collect()
+}
+
+// only nodes that came from the config can be removed by taking them out of it
+fn is_removed(url: &str, configured: &[String], configs: &[NodeConfig]) -> bool {
+    configured.iter().any(|configured| configured == url)
+        && !configs.iter().any(|config| config.url == url)
+}
+
+// applies the config file and jwt secrets as they are now
+// everything is read and checked before anything is changed, so an invalid config leaves EB as it was
+pub async fn reload(router: &NodeRouter) -> Result<ReloadReport, String> {
+    let reloader = router
+        .reloader
+        .as_ref()
+        .ok_or("This router wasn't made from a config, there's nothing to reload")?;
+    let mut configured = reloader.configured.lock().await;
+
+    let settings = config::Settings::resolve(&reloader.matches)?;
+    let router_settings = RouterSettings::new(&settings);
+
+    let general_jwt = match &settings.jwt_secret {
+        Some(general_jwt_path) => Some(
+            read_jwt(general_jwt_path).map_err(|e| format!("Error reading general jwt: {}", e))?,
+        ),
+        None => None,
+    };
+
+    let admin_auth = admin::AdminAuth::new(&settings)?;
+    if admin_auth.is_none() {
+        tracing::warn!("Neither --admin-token-file nor --jwt-secret is set, the admin api will reject every request");
+    }
+
+    let mut report = ReloadReport {
+        restart_required: reloader
+            .started_with
+            .changed(&StartupSettings::new(&settings)),
+        ..Default::default()
+    };
+
+    let mut configs = settings.nodes;
+    if let Some(node_store) = &router.node_store {
+        configs = node_store.nodes().await.merge(configs)?;
+    }
+    if configs.is_empty() {
+        return Err("No nodes left after applying the state file".to_string());
+    }
+
+    let current = router.nodes.lock().await.clone();
+    let mut added = Vec::new();
+    let mut replaced = Vec::new();
+    let mut rotated = Vec::new();
+    for config in configs.iter() {
+        match current.iter().find(|node| node.url == config.url) {
+            None => added.push(Arc::new(
+                config
+                    .clone()
+                    .create_node(general_jwt.as_ref(), router_settings.request_timeout)?,
+            )),
+            Some(node) if node_changed(node, config, router_settings.request_timeout) => {
+                let mut new_node = config
+                    .clone()
+                    .create_node(general_jwt.as_ref(), router_settings.request_timeout)?;
+                new_node.status = node.status.clone();
+                new_node.circuit = node.circuit.clone();
+                new_node.transitions = node.transitions.clone();
+                new_node.client_version = node.client_version.clone();
+                new_node.chain_verified = node.chain_verified.clone();
+                replaced.push(Arc::new(new_node));
+            }
+            Some(node) => rotated.push((node.clone(), config.jwt_key(general_jwt.as_ref())?)),
+        }
+    }
+    let (removed, kept): (Vec<Arc<Node>>, Vec<Arc<Node>>) = current
+        .into_iter()
+        .filter(|node| !configs.iter().any(|config| config.url == node.url))
+        .partition(|node| is_removed(&node.url, &configured, &configs));
+    report.kept = kept.iter().map(|node| node.url.clone()).collect();
+    rotated.extend(jwt_keys(kept, general_jwt.as_ref())?);
+
+    // a node on another chain fails the whole reload, the same as adding it through /add_nodes
+    let chain_checks = join_all(added.iter().map(|node| {
+        node.verify_chain(router.fork_config.chain_id, router.fork_config.genesis_hash)
+    }))
+    .await;
+    for result in chain_checks {
+        if let Err(ChainCheckError::WrongChain(reason)) = result {
+            return Err(reason);
+        }
+    }
+
+    *router.settings.write().unwrap() = router_settings;
+    *router.general_jwt.write().unwrap() = general_jwt;
+    *router.admin_auth.write().unwrap() = admin_auth;
+    for (node, jwt_key) in rotated {
+        node.set_jwt_key(jwt_key);
+    }
+
+    // requests already sent to replaced or removed nodes finish on the old instance
+    let mut nodes = router.nodes.lock().await;
+    for new_node in replaced {
+        if let Some(node) = nodes.iter_mut().find(|node| node.url == new_node.url) {
+            report.updated.push(new_node.url.clone());
+            *node = new_node;
+        }
+    }
+    nodes.retain(|node| !removed.iter().any(|removed| removed.url == node.url));
+    report.added = added.iter().map(|node| node.url.clone()).collect();
+    nodes.extend(added);
+    drop(nodes);
+
+    for node in removed {
+        router
+            .take_out_of_rotation(&node, SyncingStatus::Maintenance, "removed from the config")
+            .await;
+        report.removed.push(node.url.clone());
+    }
+
+    *configured = configs.iter().map(|config| config.url.clone()).collect();
+    router.recheck().await;
+
+    if !report.kept.is_empty() {
+        tracing::warn!(
+            "Keeping {} nodes added at runtime that aren't in the config, they're lost on restart without a state file",
+            report.kept.len()
+        );
+    }
+    tracing::info!(
+        "Reloaded config: {} nodes added, {} removed, {} updated",
+        report.added.len(),
+        report.removed.len(),
+        report.updated.len()
+    );
+    if !report.restart_required.is_empty() {
+        tracing::warn!(
+            "Changes to {} only take effect after a restart",
+            report.restart_required.join(", ")
+        );
+    }
+
+    Ok(report)
+}
+
+#[cfg(unix)]
+pub async fn reload_on_sighup(router: Arc<NodeRouter>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::error!("Unable to listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+
+    while hangups.recv().await.is_some() {
+        tracing::info!("Received SIGHUP, reloading config");
+        if let Err(e) = reload(&router).await {
+            tracing::error!("Config reload failed, keeping the current config: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_node_changed() {
+        let config = NodeConfig::from_spec("http://node:8551#label=geth").unwrap();
+        let jwt_key = jsonwebtoken::EncodingKey::from_secret(&[0; 32]);
+        let node = config
+            .clone()
+            .create_node(Some(&jwt_key), Duration::from_secs(1))
+            .unwrap();
+
+        assert!(!node_changed(&node, &config, Duration::from_secs(1)));
+        // the node has no timeout of its own, so a new default changes it
+        assert!(node_changed(&node, &config, Duration::from_secs(2)));
+
+        let relabelled = NodeConfig::from_spec("http://node:8551#label=nethermind").unwrap();
+        assert!(node_changed(&node, &relabelled, Duration::from_secs(1)));
+    }
+
+    #[tokio::test]
+    async fn test_runtime_nodes_kept() {
+        let configured = vec!["http://a:8551".to_string(), "http://b:8551".to_string()];
+        let configs = vec![NodeConfig::from_spec("http://a:8551").unwrap()];
+
+        assert!(!is_removed("http://a:8551", &configured, &configs));
+        // taken out of the config
+        assert!(is_removed("http://b:8551", &configured, &configs));
+        // added through /add_nodes, it was never in the config
+        assert!(!is_removed("http://c:8551", &configured, &configs));
+
+        // and signs with the new general secret after it's rotated
+        let old_secret = jsonwebtoken::EncodingKey::from_secret(&[1; 32]);
+        let runtime_node = Arc::new(
+            NodeConfig::from_spec("http://c:8551")
+                .unwrap()
+                .create_node(Some(&old_secret), Duration::from_secs(1))
+                .unwrap(),
+        );
+        let new_secret = jsonwebtoken::EncodingKey::from_secret(&[2; 32]);
+        for (node, jwt_key) in jwt_keys(vec![runtime_node.clone()], Some(&new_secret)).unwrap() {
+            node.set_jwt_key(jwt_key);
+        }
+        let token = runtime_node.make_jwt().unwrap();
+        let new_key = jsonwebtoken::DecodingKey::from_secret(&[2; 32]);
+        assert!(crate::jwt::decode_token(&token, &new_key).is_ok());
+    }
+
+}
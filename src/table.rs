@@ -0,0 +1,44 @@
+// lays out rows under their headers in left aligned columns, for the subcommands that print to a terminal
+pub fn render(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+
+    let mut out = line(headers.to_vec());
+    for row in rows {
+        out.push('\n');
+        out.push_str(&line(row.iter().map(|cell| cell.as_str()).collect()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let rows = vec![
+            vec!["http://geth:8551".to_string(), "synced".to_string()],
+            vec!["http://nm:8551".to_string(), "offline".to_string()],
+        ];
+        assert_eq!(
+            render(&["NODE", "STATUS"], &rows),
+            "NODE              STATUS\n\
+             http://geth:8551  synced\n\
+             http://nm:8551    offline"
+        );
+    }
+}
@@ -0,0 +1,420 @@
+use crate::{client_versions, NodeRouter};
+use serde::Serialize;
+use std::{fmt, sync::Arc};
+use tokio::time::{Duration, Instant};
+use types::{node::Node, *};
+
+// how many recent newPayload and fcU requests are kept to replay to upgraded nodes
+pub const REPLAY_LEN: usize = 64;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// health checks that have to fail in a row before the node counts as shut down, unless it refuses connections
+const SHUTDOWN_FAILURES: u32 = 3;
+
+// how long a node can take in each stage before the upgrade is given up on
+const STAGE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "stage", content = "reason")]
+pub enum UpgradeStage {
+    // out of voting, waiting for it to go offline, or to come back on another version once the operator says it's being upgraded
+    WaitingForShutdown,
+    // waiting for the node to answer health checks again
+    WaitingForRestart,
+    // sending the node the recent newPayload and fcU requests it missed
+    Replaying,
+    // waiting for the node to be synced and have the same head as the majority
+    WaitingForSync,
+    // the node is back in rotation
+    Completed,
+    // the node is left in maintenance
+    Failed(String),
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct UpgradeProgress {
+    pub node: String,
+    #[serde(flatten)]
+    pub stage: UpgradeStage,
+    // set once the operator says the node is being upgraded, so a restart too quick to see it offline is noticed by its new version
+    pub operator_signalled: bool,
+    pub replayed: usize,
+    // unix time in milliseconds
+    pub started: i64,
+    pub updated: i64,
+}
+
+impl UpgradeProgress {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.stage,
+            UpgradeStage::Completed | UpgradeStage::Failed(_)
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum UpgradeError {
+    NotFound,
+    AlreadyRunning(String),
+    NotWaitingForShutdown,
+}
+
+impl fmt::Display for UpgradeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpgradeError::NotFound => write!(f, "No node with that url or label"),
+            UpgradeError::AlreadyRunning(node) => {
+                write!(f, "An upgrade is already running for {}", node)
+            }
+            UpgradeError::NotWaitingForShutdown => {
+                write!(f, "Node is not waiting for an upgrade signal")
+            }
+        }
+    }
+}
+
+// the request as it should be replayed, fcU payload attributes are dropped so the node doesn't start building blocks
+pub fn replayable(request: &RpcRequest) -> RpcRequest {
+    let mut request = request.clone();
+    if matches!(
+        request.method,
+        EngineMethod::engine_forkchoiceUpdatedV1
+            | EngineMethod::engine_forkchoiceUpdatedV2
+            | EngineMethod::engine_forkchoiceUpdatedV3
+    ) {
+        if let Some(payload_attributes) = request.params.get_mut(1) {
+            *payload_attributes = serde_json::Value::Null;
+        }
+    }
+    request
+}
+
+// takes the node out of voting and walks it through the upgrade in the background
+// only one node is upgraded at a time
+pub async fn start_upgrade(
+    router: Arc<NodeRouter>,
+    name: &str,
+) -> Result<UpgradeProgress, UpgradeError> {
+    let node = router.find_node(name).await.ok_or(UpgradeError::NotFound)?;
+
+    let mut upgrades = router.upgrades.write().await;
+    if let Some(running) = upgrades.values().find(|progress| !progress.is_finished()) {
+        return Err(UpgradeError::AlreadyRunning(running.node.clone()));
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let progress = UpgradeProgress {
+        node: node.url.clone(),
+        stage: UpgradeStage::WaitingForShutdown,
+        operator_signalled: false,
+        replayed: 0,
+        started: now,
+        updated: now,
+    };
+    upgrades.insert(node.url.clone(), progress.clone());
+    drop(upgrades);
+
+    router
+        .take_out_of_rotation(&node, SyncingStatus::Maintenance, "rolling upgrade started")
+        .await;
+    tracing::info!("Started rolling upgrade of {}", node.url);
+
+    tokio::spawn(async move {
+        let stage = match drive_upgrade(&router, &node).await {
+            Ok(()) => {
+                tracing::info!("Rolling upgrade of {} completed", node.url);
+                UpgradeStage::Completed
+            }
+            Err(reason) => {
+                tracing::error!("Rolling upgrade of {} failed: {}", node.url, reason);
+                UpgradeStage::Failed(reason)
+            }
+        };
+        set_stage(&router, &node, stage).await;
+    });
+
+    Ok(progress)
+}
+
+// the operator's signal that the node is being upgraded
+pub async fn continue_upgrade(
+    router: &NodeRouter,
+    name: &str,
+) -> Result<UpgradeProgress, UpgradeError> {
+    let node = router.find_node(name).await.ok_or(UpgradeError::NotFound)?;
+
+    let mut upgrades = router.upgrades.write().await;
+    match upgrades.get_mut(&node.url) {
+        Some(progress) if progress.stage == UpgradeStage::WaitingForShutdown => {
+            progress.operator_signalled = true;
+            progress.updated = chrono::Utc::now().timestamp_millis();
+            Ok(progress.clone())
+        }
+        _ => Err(UpgradeError::NotWaitingForShutdown),
+    }
+}
+
+async fn set_stage(router: &NodeRouter, node: &Node, stage: UpgradeStage) {
+    if let Some(progress) = router.upgrades.write().await.get_mut(&node.url) {
+        tracing::debug!("Rolling upgrade of {} is now {:?}", node.url, stage);
+        progress.stage = stage;
+        progress.updated = chrono::Utc::now().timestamp_millis();
+    }
+}
+
+async fn operator_signalled(router: &NodeRouter, node: &Node) -> bool {
+    router
+        .upgrades
+        .read()
+        .await
+        .get(&node.url)
+        .is_some_and(|progress| progress.operator_signalled)
+}
+
+// whether the node has gone down for its upgrade
+// a single failed health check can be a blip, nothing listening on the port anymore is a sure sign
+// the operator's signal alone isn't enough, a node still answering on the version it had before hasn't been upgraded yet
+fn has_shut_down(
+    failures: u32,
+    refused: bool,
+    operator_signalled: bool,
+    version_before: Option<&ClientVersionV1>,
+    version_now: Option<&ClientVersionV1>,
+) -> bool {
+    refused
+        || failures >= SHUTDOWN_FAILURES
+        || (operator_signalled && version_now.is_some() && version_now != version_before)
+}
+
+fn is_connection_refused(e: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(e);
+    while let Some(err) = source {
+        if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
+            if io_err.kind() == std::io::ErrorKind::ConnectionRefused {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+    false
+}
+
+// whether the node's head is close enough to the majority's to vote again
+// the majority head is from the last recheck, so the node may well be a few blocks ahead of it or behind it
+fn caught_up(head: Option<BlockHead>, majority_head: Option<BlockHead>, max_head_lag: u64) -> bool {
+    match (head, majority_head) {
+        // nothing to compare to
+        (_, None) => true,
+        (Some(head), Some(majority_head)) => {
+            majority_head.number.saturating_sub(head.number) <= max_head_lag
+        }
+        (None, Some(_)) => false,
+    }
+}
+
+async fn drive_upgrade(router: &Arc<NodeRouter>, node: &Arc<Node>) -> Result<(), String> {
+    let own_version = client_versions::own_client_version();
+    let version_before = match node.get_client_version(&own_version).await {
+        Ok(version) => version,
+        Err(_) => node.client_version.read().await.clone(),
+    };
+
+    let deadline = Instant::now() + STAGE_TIMEOUT;
+    let mut failures = 0;
+    loop {
+        let refused = match node.check_status().await {
+            Ok(_) => {
+                failures = 0;
+                false
+            }
+            Err(e) => {
+                failures += 1;
+                is_connection_refused(&e)
+            }
+        };
+        let signalled = operator_signalled(router, node).await;
+        let version_now = if failures == 0 && signalled {
+            node.get_client_version(&own_version).await.ok().flatten()
+        } else {
+            None
+        };
+        if has_shut_down(
+            failures,
+            refused,
+            signalled,
+            version_before.as_ref(),
+            version_now.as_ref(),
+        ) {
+            break;
+        }
+        if Instant::now() > deadline {
+            return Err("timed out waiting for the node to go offline".to_string());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    set_stage(router, node, UpgradeStage::WaitingForRestart).await;
+    let deadline = Instant::now() + STAGE_TIMEOUT;
+    loop {
+        if let Ok(status) = node.check_status().await {
+            if status.status != SyncingStatus::AuthFailed {
+                break;
+            }
+        }
+        if Instant::now() > deadline {
+            return Err("timed out waiting for the node to come back".to_string());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    set_stage(router, node, UpgradeStage::Replaying).await;
+    let recent_payloads = router.recent_payloads.lock().await.clone();
+    let mut replayed = 0;
+    for request in recent_payloads.iter() {
+        let jwt_token = match node.make_jwt() {
+            Ok(jwt_token) => jwt_token,
+            Err(e) => return Err(format!("could not make jwt: {}", e)),
+        };
+
+        match node
+            .do_request_no_timeout(request, format!("Bearer {}", jwt_token))
+            .await
+        {
+            Ok(_) => replayed += 1,
+            Err(e) => tracing::warn!(
+                "Could not replay {:?} to {}: {}",
+                request.method,
+                node.url,
+                e
+            ),
+        }
+    }
+    if let Some(progress) = router.upgrades.write().await.get_mut(&node.url) {
+        progress.replayed = replayed;
+    }
+
+    set_stage(router, node, UpgradeStage::WaitingForSync).await;
+    let deadline = Instant::now() + STAGE_TIMEOUT;
+    loop {
+        if let Ok(status) = node.check_status().await {
+            if status.status == SyncingStatus::Synced
+                && caught_up(
+                    status.head,
+                    router.majority_head().await,
+                    router.settings().max_head_lag,
+                )
+            {
+                break;
+            }
+        }
+        if Instant::now() > deadline {
+            return Err("timed out waiting for the node to sync with the other nodes".to_string());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    if let Err(e) = router.enable_node(&node.url).await {
+        return Err(format!("could not enable node: {}", e));
+    }
+    router.recheck().await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_replayable_drops_payload_attributes() {
+        let request: RpcRequest = serde_json::from_value(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "engine_forkchoiceUpdatedV3",
+            "params": [{"headBlockHash": "0x01"}, {"timestamp": "0x1"}]
+        }))
+        .unwrap();
+
+        let replayed = replayable(&request);
+        assert_eq!(replayed.params, json!([{"headBlockHash": "0x01"}, null]));
+    }
+
+    #[test]
+    fn test_signal_before_shutdown() {
+        let old = ClientVersionV1 {
+            code: "GE".to_string(),
+            name: "Geth".to_string(),
+            version: "1.14.0".to_string(),
+            commit: "0x01020304".to_string(),
+        };
+        let new = ClientVersionV1 {
+            version: "1.14.1".to_string(),
+            ..old.clone()
+        };
+
+        // signalled before the old node was stopped, it's still answering on the old version
+        assert!(!has_shut_down(0, false, true, Some(&old), Some(&old)));
+        assert!(!has_shut_down(0, false, true, Some(&old), None));
+        assert!(!has_shut_down(0, false, true, None, None));
+        // then it goes offline, or was restarted between polls and is on the new version
+        assert!(has_shut_down(0, true, true, Some(&old), None));
+        assert!(has_shut_down(0, false, true, Some(&old), Some(&new)));
+        assert!(has_shut_down(0, false, true, None, Some(&new)));
+
+        // without the signal only going offline counts
+        assert!(!has_shut_down(0, false, false, Some(&old), Some(&new)));
+        assert!(has_shut_down(1, true, false, Some(&old), None));
+
+        // a failed health check that isn't a refused connection has to happen a few times in a row
+        assert!(!has_shut_down(1, false, false, Some(&old), None));
+        assert!(!has_shut_down(SHUTDOWN_FAILURES - 1, false, true, Some(&old), None));
+        assert!(has_shut_down(SHUTDOWN_FAILURES, false, false, Some(&old), None));
+    }
+
+    #[test]
+    fn test_caught_up() {
+        let head = |number| BlockHead {
+            number,
+            hash: Default::default(),
+        };
+
+        assert!(caught_up(Some(head(100)), None, 8));
+        assert!(caught_up(Some(head(100)), Some(head(100)), 8));
+        // within the lag of a majority head that moved on, or ahead of a stale one
+        assert!(caught_up(Some(head(95)), Some(head(103)), 8));
+        assert!(caught_up(Some(head(105)), Some(head(103)), 8));
+        assert!(!caught_up(Some(head(94)), Some(head(103)), 8));
+        assert!(!caught_up(None, Some(head(103)), 8));
+    }
+
+    #[tokio::test]
+    async fn test_connection_refused() {
+        // nothing listens on port 1
+        let e = reqwest::Client::new()
+            .get("http://127.0.0.1:1")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(is_connection_refused(&e));
+    }
+
+    #[test]
+    fn test_progress_serialization() {
+        let progress = UpgradeProgress {
+            node: "http://node:8551".to_string(),
+            stage: UpgradeStage::Failed("timed out".to_string()),
+            operator_signalled: true,
+            replayed: 3,
+            started: 1,
+            updated: 2,
+        };
+
+        let progress = serde_json::to_value(progress).unwrap();
+        assert_eq!(progress["stage"], json!("failed"));
+        assert_eq!(progress["reason"], json!("timed out"));
+        assert_eq!(progress["node"], json!("http://node:8551"));
+    }
+}
@@ -61,10 +61,11 @@ pub fn rlp_encode_withdrawal(withdrawal: &Withdrawal) -> Vec<u8> {
 }
 
 // Thank you lighthouse team again! https://github.com/sigp/lighthouse/blob/stable/beacon_node/execution_layer/src/block_hash.rs#L17-L48
-pub fn verify_payload_block_hash(
+/// Build the execution block header a payload stands for.
+pub fn payload_block_header(
     payload: &ExecutionPayload,
     parent_beacon_block_root: Option<H256>,
-) -> Result<(), Box<dyn Error>> {
+) -> ExecutionBlockHeader {
     // Calculate the transactions root.
     // We're currently using a deprecated Parity library for this. We should move to a
     // better alternative when one appears, possibly following Reth.
@@ -87,7 +88,7 @@ pub fn verify_payload_block_hash(
     let rlp_excess_blob_gas = payload.excess_blob_gas().ok();
 
     // Construct the block header.
-    let exec_block_header = ExecutionBlockHeader::from_payload(
+    ExecutionBlockHeader::from_payload(
         payload,
         KECCAK_EMPTY_LIST_RLP.as_fixed_bytes().into(),
         rlp_transactions_root,
@@ -95,11 +96,20 @@ pub fn verify_payload_block_hash(
         rlp_blob_gas_used,
         rlp_excess_blob_gas,
         parent_beacon_block_root,
-    );
+    )
+}
+
+/// Hash the RLP encoding of a block header.
+pub fn block_header_hash(rlp_block_header: &[u8]) -> H256 {
+    H256(keccak256(rlp_block_header).into())
+}
 
-    // Hash the RLP encoding of the block header.
-    let rlp_block_header = rlp_encode_block_header(&exec_block_header);
-    let header_hash = H256(keccak256(&rlp_block_header).into());
+pub fn verify_payload_block_hash(
+    payload: &ExecutionPayload,
+    parent_beacon_block_root: Option<H256>,
+) -> Result<(), Box<dyn Error>> {
+    let exec_block_header = payload_block_header(payload, parent_beacon_block_root);
+    let header_hash = block_header_hash(&rlp_encode_block_header(&exec_block_header));
 
     if header_hash != payload.block_hash() {
         return Err(format!(
@@ -0,0 +1,213 @@
+use crate::{table, verify_hash};
+use ethereum_types::H256;
+use std::io::Read;
+use types::*;
+
+// the payload in a newPayload request or a bare ExecutionPayload, with the parent beacon block root if there is one
+// the payload's version is told from its fields, so it doesn't matter which fork the network is on
+pub fn read_payload(
+    mut json: serde_json::Value,
+) -> Result<(ExecutionPayload, Option<H256>), String> {
+    let (mut payload, parent_beacon_block_root) = match json.get_mut("params") {
+        Some(params) => {
+            let params = params
+                .as_array_mut()
+                .ok_or("The request's params are not an array")?;
+            if params.is_empty() {
+                return Err("The request has no execution payload".to_string());
+            }
+            // newPayloadV3's params are [ExecutionPayloadV3, expectedBlobVersionedHashes, ParentBeaconBlockRoot]
+            let parent_beacon_block_root = params.get_mut(2).map(serde_json::Value::take);
+            (params[0].take(), parent_beacon_block_root)
+        }
+        None => {
+            let parent_beacon_block_root = json
+                .as_object_mut()
+                .ok_or("The file is not a json object")?
+                .remove("parentBeaconBlockRoot");
+            (json, parent_beacon_block_root)
+        }
+    };
+
+    let parent_beacon_block_root = parent_beacon_block_root
+        .map(serde_json::from_value::<H256>)
+        .transpose()
+        .map_err(|e| format!("Invalid parent beacon block root: {}", e))?;
+
+    let execution_payload = if payload.get("blobGasUsed").is_some() {
+        serde_json::from_value(payload.take()).map(ExecutionPayload::V3)
+    } else if payload.get("withdrawals").is_some() {
+        serde_json::from_value(payload.take()).map(ExecutionPayload::V2)
+    } else {
+        serde_json::from_value(payload.take()).map(ExecutionPayload::V1)
+    }
+    .map_err(|e| format!("Could not read the execution payload: {}", e))?;
+
+    Ok((execution_payload, parent_beacon_block_root))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+// the computed header, roots and hash as rows to print
+fn report_rows(header: &ExecutionBlockHeader, rlp: &[u8], block_hash: H256) -> Vec<Vec<String>> {
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let rows = [
+        ("parent hash", format!("{:?}", header.parent_hash)),
+        ("ommers hash", format!("{:?}", header.ommers_hash)),
+        ("fee recipient", format!("{:?}", header.beneficiary)),
+        ("state root", format!("{:?}", header.state_root)),
+        (
+            "transactions root",
+            format!("{:?}", header.transactions_root),
+        ),
+        ("receipts root", format!("{:?}", header.receipts_root)),
+        ("logs bloom", hex(&header.logs_bloom)),
+        ("difficulty", header.difficulty.to_string()),
+        ("number", header.number.to_string()),
+        ("gas limit", header.gas_limit.to_string()),
+        ("gas used", header.gas_used.to_string()),
+        ("timestamp", header.timestamp.to_string()),
+        ("extra data", hex(&header.extra_data)),
+        ("prev randao", format!("{:?}", header.mix_hash)),
+        ("nonce", format!("{:?}", header.nonce)),
+        ("base fee per gas", header.base_fee_per_gas.to_string()),
+        (
+            "withdrawals root",
+            optional(header.withdrawals_root.map(|root| format!("{:?}", root))),
+        ),
+        (
+            "blob gas used",
+            optional(header.blob_gas_used.map(|gas| gas.to_string())),
+        ),
+        (
+            "excess blob gas",
+            optional(header.excess_blob_gas.map(|gas| gas.to_string())),
+        ),
+        (
+            "parent beacon block root",
+            optional(
+                header
+                    .parent_beacon_block_root
+                    .map(|root| format!("{:?}", root)),
+            ),
+        ),
+        ("header rlp", hex(rlp)),
+        ("computed block hash", format!("{:?}", block_hash)),
+    ];
+    rows.into_iter()
+        .map(|(field, value)| vec![field.to_string(), value])
+        .collect()
+}
+
+// prints the header EB computes for the payload and whether its hash matches the payload's block hash
+// returns false if it doesn't match or the payload can't be read
+pub fn run(matches: &clap::ArgMatches<'_>) -> bool {
+    match verify(matches) {
+        Ok(matched) => matched,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
+        }
+    }
+}
+
+fn verify(matches: &clap::ArgMatches<'_>) -> Result<bool, String> {
+    // clap makes sure the file is given
+    let path = matches.value_of("file").unwrap();
+    let mut contents = String::new();
+    if path == "-" {
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .map_err(|e| format!("Could not read stdin: {}", e))?;
+    } else {
+        contents =
+            std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    }
+    let json: serde_json::Value =
+        serde_json::from_str(&contents).map_err(|e| format!("Invalid json in {}: {}", path, e))?;
+
+    let (payload, mut parent_beacon_block_root) = read_payload(json)?;
+    if let Some(root) = matches.value_of("parent-beacon-block-root") {
+        parent_beacon_block_root = Some(
+            root.parse()
+                .map_err(|e| format!("Invalid value for --parent-beacon-block-root: {}", e))?,
+        );
+    }
+
+    let header = verify_hash::payload_block_header(&payload, parent_beacon_block_root);
+    let rlp = verify_hash::rlp_encode_block_header(&header);
+    let block_hash = verify_hash::block_header_hash(&rlp);
+
+    let mut rows = report_rows(&header, &rlp, block_hash);
+    rows.push(vec![
+        "payload block hash".to_string(),
+        format!("{:?}", payload.block_hash()),
+    ]);
+    println!("{}", table::render(&["FIELD", "VALUE"], &rows));
+    println!();
+
+    if block_hash == payload.block_hash() {
+        println!("Block hash matches");
+        Ok(true)
+    } else {
+        println!("Block hash does NOT match");
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_read_payload() {
+        let payload_v1 = json!({
+            "parentHash": format!("{:?}", H256::zero()),
+            "feeRecipient": "0x0000000000000000000000000000000000000000",
+            "stateRoot": format!("{:?}", H256::zero()),
+            "receiptsRoot": format!("{:?}", H256::zero()),
+            "logsBloom": format!("0x{}", "00".repeat(256)),
+            "prevRandao": format!("{:?}", H256::zero()),
+            "blockNumber": "0x1",
+            "gasLimit": "0x1c9c380",
+            "gasUsed": "0x0",
+            "timestamp": "0x5f5e100",
+            "extraData": "0x",
+            "baseFeePerGas": "0x7",
+            "blockHash": format!("{:?}", H256::zero()),
+            "transactions": []
+        });
+        let root = H256::repeat_byte(1);
+
+        let (payload, parent_beacon_block_root) = read_payload(payload_v1.clone()).unwrap();
+        assert!(matches!(payload, ExecutionPayload::V1(_)));
+        assert_eq!(parent_beacon_block_root, None);
+
+        // a bare payload can carry its parent beacon block root next to its fields
+        let mut payload_v3 = payload_v1.clone();
+        payload_v3["withdrawals"] = json!([]);
+        payload_v3["blobGasUsed"] = json!("0x0");
+        payload_v3["excessBlobGas"] = json!("0x0");
+        payload_v3["parentBeaconBlockRoot"] = json!(format!("{:?}", root));
+        let (payload, parent_beacon_block_root) = read_payload(payload_v3.clone()).unwrap();
+        assert!(matches!(payload, ExecutionPayload::V3(_)));
+        assert_eq!(parent_beacon_block_root, Some(root));
+
+        payload_v3
+            .as_object_mut()
+            .unwrap()
+            .remove("parentBeaconBlockRoot");
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "engine_newPayloadV3",
+            "params": [payload_v3, [], format!("{:?}", root)]
+        });
+        let (payload, parent_beacon_block_root) = read_payload(request).unwrap();
+        assert!(matches!(payload, ExecutionPayload::V3(_)));
+        assert_eq!(parent_beacon_block_root, Some(root));
+    }
+}
@@ -0,0 +1,78 @@
+use executionbackup::{types::*, NodeRouter, RouterSettings};
+use std::{sync::Arc, time::Duration};
+
+// nothing listens on port 1, so the node is always offline
+fn offline_node() -> Vec<Arc<node::Node>> {
+    let config = NodeConfig::from_spec("http://127.0.0.1:1#label=offline").unwrap();
+    let jwt_key = jsonwebtoken::EncodingKey::from_secret(&[0; 32]);
+    create_nodes(vec![config], Some(&jwt_key), Duration::from_millis(500)).unwrap()
+}
+
+#[tokio::test]
+async fn test_router_with_offline_node() {
+    assert!(NodeRouter::builder(Vec::new()).build().is_err());
+
+    let router = Arc::new(
+        NodeRouter::builder(offline_node())
+            .settings(RouterSettings {
+                majority_percentage: 0.5,
+                ..Default::default()
+            })
+            .fork_config(ForkConfig::holesky())
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(router.settings().majority_percentage, 0.5);
+
+    router.recheck().await;
+    let report = router.metrics_report().await.unwrap();
+    assert_eq!(
+        report["dead_nodes"],
+        serde_json::json!(["http://127.0.0.1:1"])
+    );
+    assert_eq!(report["labels"]["http://127.0.0.1:1"], "offline");
+
+    let (resp, status) = router
+        .handle_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"eth_chainId","params":[]}"#.to_string(),
+            Some("Bearer token"),
+        )
+        .await;
+    assert_eq!(status, 500);
+    let resp: serde_json::Value = serde_json::from_str(&resp).unwrap();
+    assert_eq!(resp["id"], 1);
+    assert_eq!(resp["error"]["message"], "No nodes available");
+
+    // engine requests are refused before any node is asked if the CL didn't sign them
+    let (_, status) = router
+        .handle_request(
+            r#"{"jsonrpc":"2.0","id":2,"method":"engine_exchangeCapabilities","params":[[]]}"#
+                .to_string(),
+            None,
+        )
+        .await;
+    assert_eq!(status, 400);
+    assert!(router.disagreements().is_empty());
+
+    // notifications in a batch are routed but not answered
+    let (resp, status) = router
+        .handle_request(
+            r#"[{"jsonrpc":"2.0","id":3,"method":"eth_chainId","params":[]},{"jsonrpc":"2.0","method":"eth_chainId","params":[]}]"#
+                .to_string(),
+            None,
+        )
+        .await;
+    assert_eq!(status, 200);
+    let resp: serde_json::Value = serde_json::from_str(&resp).unwrap();
+    assert_eq!(resp.as_array().unwrap().len(), 1);
+    assert_eq!(resp[0]["id"], 3);
+
+    let (resp, status) = router
+        .handle_request(
+            r#"[{"jsonrpc":"2.0","method":"eth_chainId","params":[]}]"#.to_string(),
+            None,
+        )
+        .await;
+    assert_eq!(status, 204);
+    assert!(resp.is_empty());
+}
@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    // engine calls are sent to the node
+    Closed,
+    // the node failed too many engine calls in a row, nothing is sent until the open duration passes
+    Open,
+    // the open duration passed, a single probe call is let through to see if the node recovered
+    // a probe that never reports back (the CL went away mid-call) is given up on after another open duration
+    HalfOpen,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CircuitBreakerConfig {
+    // consecutive failed engine calls before the circuit opens
+    pub failure_threshold: u32,
+    // how long the circuit stays open before probing the node again
+    pub open_duration: Duration,
+}
+
+struct CircuitInner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Instant,
+    probe_in_flight: bool,
+    probe_started_at: Instant,
+}
+
+impl CircuitInner {
+    fn start_probe(&mut self, now: Instant) {
+        self.probe_in_flight = true;
+        self.probe_started_at = now;
+    }
+
+    // a probe whose result never came back doesn't hold the circuit forever
+    fn probe_pending(&self, config: &CircuitBreakerConfig, now: Instant) -> bool {
+        self.probe_in_flight && now.duration_since(self.probe_started_at) < config.open_duration
+    }
+}
+
+pub struct CircuitBreaker {
+    inner: Mutex<CircuitInner>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        CircuitBreaker {
+            inner: Mutex::new(CircuitInner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: Instant::now(),
+                probe_in_flight: false,
+                probe_started_at: Instant::now(),
+            }),
+        }
+    }
+}
+
+impl CircuitBreaker {
+    pub fn state(&self) -> CircuitState {
+        self.inner.lock().unwrap().state
+    }
+
+    // whether the node shouldn't be sent anything right now, doesn't use up the half-open probe
+    pub fn is_open(&self, config: &CircuitBreakerConfig) -> bool {
+        self.is_open_at(config, Instant::now())
+    }
+
+    // whether an engine call can be sent to the node
+    // moves an open circuit to half-open once the open duration passes, letting this call through as the probe
+    pub fn allow_request(&self, config: &CircuitBreakerConfig) -> bool {
+        self.allow_request_at(config, Instant::now())
+    }
+
+    // returns the new state if the call closed the circuit
+    pub fn record_success(&self) -> Option<CircuitState> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = 0;
+        inner.probe_in_flight = false;
+
+        if inner.state != CircuitState::Closed {
+            inner.state = CircuitState::Closed;
+            return Some(CircuitState::Closed);
+        }
+        None
+    }
+
+    // returns the new state if the call opened the circuit
+    pub fn record_failure(&self, config: &CircuitBreakerConfig) -> Option<CircuitState> {
+        self.record_failure_at(config, Instant::now())
+    }
+
+    fn is_open_at(&self, config: &CircuitBreakerConfig, now: Instant) -> bool {
+        let inner = self.inner.lock().unwrap();
+        match inner.state {
+            CircuitState::Closed => false,
+            CircuitState::Open => now.duration_since(inner.opened_at) < config.open_duration,
+            CircuitState::HalfOpen => inner.probe_pending(config, now),
+        }
+    }
+
+    fn allow_request_at(&self, config: &CircuitBreakerConfig, now: Instant) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => {
+                if now.duration_since(inner.opened_at) < config.open_duration {
+                    return false;
+                }
+                inner.state = CircuitState::HalfOpen;
+                inner.start_probe(now);
+                true
+            }
+            CircuitState::HalfOpen => {
+                if inner.probe_pending(config, now) {
+                    return false;
+                }
+                inner.start_probe(now);
+                true
+            }
+        }
+    }
+
+    fn record_failure_at(
+        &self,
+        config: &CircuitBreakerConfig,
+        now: Instant,
+    ) -> Option<CircuitState> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        inner.probe_in_flight = false;
+
+        let should_open = match inner.state {
+            CircuitState::Closed => inner.consecutive_failures >= config.failure_threshold,
+            CircuitState::HalfOpen => true, // probe failed
+            CircuitState::Open => false,
+        };
+
+        if should_open {
+            inner.state = CircuitState::Open;
+            inner.opened_at = now;
+            return Some(CircuitState::Open);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 3,
+            open_duration: Duration::from_secs(30),
+        };
+        let circuit = CircuitBreaker::default();
+        let start = Instant::now();
+
+        // failures below the threshold keep it closed, a success resets the count
+        assert_eq!(circuit.record_failure_at(&config, start), None);
+        assert_eq!(circuit.record_failure_at(&config, start), None);
+        assert_eq!(circuit.record_success(), None);
+        assert_eq!(circuit.record_failure_at(&config, start), None);
+        assert_eq!(circuit.record_failure_at(&config, start), None);
+        assert_eq!(
+            circuit.record_failure_at(&config, start),
+            Some(CircuitState::Open)
+        );
+
+        assert!(circuit.is_open_at(&config, start + Duration::from_secs(10)));
+        assert!(!circuit.allow_request_at(&config, start + Duration::from_secs(10)));
+
+        // after the open duration, only one probe is let through
+        let later = start + Duration::from_secs(31);
+        assert!(!circuit.is_open_at(&config, later));
+        assert!(circuit.allow_request_at(&config, later));
+        assert_eq!(circuit.state(), CircuitState::HalfOpen);
+        assert!(!circuit.allow_request_at(&config, later));
+
+        // a failed probe opens it again
+        assert_eq!(
+            circuit.record_failure_at(&config, later),
+            Some(CircuitState::Open)
+        );
+        assert!(circuit.is_open_at(&config, later + Duration::from_secs(10)));
+
+        // a successful probe closes it
+        let even_later = later + Duration::from_secs(31);
+        assert!(circuit.allow_request_at(&config, even_later));
+        assert_eq!(circuit.record_success(), Some(CircuitState::Closed));
+        assert!(circuit.allow_request_at(&config, even_later));
+    }
+
+    #[test]
+    fn test_abandoned_probe() {
+        let config = CircuitBreakerConfig {
+            failure_threshold: 1,
+            open_duration: Duration::from_secs(30),
+        };
+        let circuit = CircuitBreaker::default();
+        let start = Instant::now();
+        assert_eq!(
+            circuit.record_failure_at(&config, start),
+            Some(CircuitState::Open)
+        );
+
+        // the probe is let through, but its request is dropped before it records anything
+        let probe = start + Duration::from_secs(31);
+        assert!(circuit.allow_request_at(&config, probe));
+        assert!(circuit.is_open_at(&config, probe + Duration::from_secs(10)));
+        assert!(!circuit.allow_request_at(&config, probe + Duration::from_secs(10)));
+
+        // after another open duration the node is probed again
+        let retry = probe + Duration::from_secs(31);
+        assert!(!circuit.is_open_at(&config, retry));
+        assert!(circuit.allow_request_at(&config, retry));
+        assert_eq!(circuit.state(), CircuitState::HalfOpen);
+        assert!(!circuit.allow_request_at(&config, retry));
+        assert_eq!(circuit.record_success(), Some(CircuitState::Closed));
+    }
+}
@@ -9,18 +9,23 @@ use ssz_types::{
     VariableList,
 };
 use std::{collections::HashMap, sync::Arc};
+pub mod circuit;
 pub mod keccak;
 use superstruct::superstruct;
 pub mod node;
+pub mod state;
 use lazy_static::lazy_static;
 use node::*;
-use regex::Regex;
 use tokio::time::Duration;
 
+// max requests waiting to be replicated to a single node
+const DISPATCH_QUEUE_SIZE: usize = 256;
+
 const DEFAULT_ALGORITHM: jsonwebtoken::Algorithm = jsonwebtoken::Algorithm::HS256;
 
 lazy_static! {
     static ref TIMEOUT: Duration = Duration::from_millis(7500);
+    static ref DISPATCH_TIMEOUT: Duration = Duration::from_secs(60);
     static ref JWT_HEADER: jsonwebtoken::Header = jsonwebtoken::Header::new(DEFAULT_ALGORITHM);
 }
 
@@ -34,6 +39,11 @@ pub fn make_jwt(
 }
 
 pub fn read_jwt(path: &str) -> Result<jsonwebtoken::EncodingKey, String> {
+    Ok(EncodingKey::from_secret(&read_jwt_secret(path)?))
+}
+
+// the raw secret from a hex encoded jwt secret file
+pub fn read_jwt_secret(path: &str) -> Result<Vec<u8>, String> {
     let jwt_secret =
         std::fs::read_to_string(path).map_err(|e| format!("Error reading jwt file: {}", e))?;
     let jwt_secret = jwt_secret.trim().to_string();
@@ -44,9 +54,7 @@ pub fn read_jwt(path: &str) -> Result<jsonwebtoken::EncodingKey, String> {
         .unwrap_or(&jwt_secret)
         .to_string();
 
-    Ok(EncodingKey::from_secret(
-        &hex::decode(jwt_secret).map_err(|e| format!("Could not decode JWT: {}", e))?,
-    ))
+    hex::decode(jwt_secret).map_err(|e| format!("Could not decode JWT: {}", e))
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, Eq, Hash)]
@@ -107,6 +115,9 @@ pub struct ForkConfig {
     //  pub MERGE_FORK_EPOCH: Option<u64> = Some(144896);
     pub shanghai_fork_epoch: Option<u64>,
     pub cancun_fork_epoch: Option<u64>,
+    // every node has to be on this chain to be let in
+    pub chain_id: u64,
+    pub genesis_hash: H256,
 }
 
 impl ForkConfig {
@@ -114,6 +125,10 @@ impl ForkConfig {
         ForkConfig {
             shanghai_fork_epoch: Some(194048),
             cancun_fork_epoch: Some(269568),
+            chain_id: 1,
+            genesis_hash: H256(array_bytes::hex2array_unchecked(
+                "d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3",
+            )),
         }
     }
 
@@ -121,6 +136,10 @@ impl ForkConfig {
         ForkConfig {
             shanghai_fork_epoch: Some(256),
             cancun_fork_epoch: Some(29696),
+            chain_id: 17000,
+            genesis_hash: H256(array_bytes::hex2array_unchecked(
+                "b5f7f912443c940f21fd611f12828d75b534364ed9e95ca4e307729a4661bde4",
+            )),
         }
     }
 }
@@ -262,12 +281,25 @@ pub struct Claims {
     pub iat: i64,
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SyncingStatus {
     Synced,
     Offline,
     OnlineAndSyncing,
     NodeNotInitialized,
+    // says it's synced, but its head is too far behind the other nodes
+    Lagging,
+    // kept out while its circuit is open after failing engine calls
+    Quarantined,
+    // taken out of rotation by an admin
+    Maintenance,
+    // online, but rejects our jwt
+    AuthFailed,
+    // answers for a different chain than the one EB is configured for
+    WrongChain,
+    // online, but hasn't told us its chain id and genesis hash yet
+    ChainUnverified,
 }
 
 #[derive(Debug)]
@@ -277,6 +309,43 @@ pub enum FcuLogicError {
     NoResponses,
 }
 
+#[derive(Debug)]
+pub enum NodeAdminError {
+    NotFound,
+    NotDisabled,
+    LastNode,
+}
+
+impl std::fmt::Display for NodeAdminError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeAdminError::NotFound => write!(f, "No node with that url or label"),
+            NodeAdminError::NotDisabled => write!(f, "Node is not disabled"),
+            NodeAdminError::LastNode => write!(f, "Can't remove the last node"),
+        }
+    }
+}
+
+// why a node couldn't be let in after checking which chain it's on
+#[derive(Debug)]
+pub enum ChainCheckError {
+    // the node couldn't be asked or gave an answer we can't read, it's checked again later
+    Unverified(String),
+    // the node is on another chain and is kept out
+    WrongChain(String),
+}
+
+impl std::fmt::Display for ChainCheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChainCheckError::Unverified(reason) => {
+                write!(f, "could not verify chain: {}", reason)
+            }
+            ChainCheckError::WrongChain(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ParseError {
     MethodNotFound,
@@ -284,13 +353,23 @@ pub enum ParseError {
     NoId,
     InvalidJson,
     NoParams,
-    ElError,
+    // the error object the node answered with
+    ElError(serde_json::Value),
 }
 
 #[derive(Clone)]
 pub struct NodeHealth {
+    // the node's state in the router, except in health returned by Node::check_status where it's what the node reported
     pub status: SyncingStatus,
     pub resp_time: u128,
+    pub head: Option<BlockHead>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BlockHead {
+    #[serde(with = "serde_utils::u64_hex_be")]
+    pub number: u64,
+    pub hash: H256,
 }
 
 pub struct NodeTiming {
@@ -314,13 +393,30 @@ pub enum EngineMethod {
     engine_forkchoiceUpdatedV3,
     engine_getPayloadV3,
     engine_getClientVersionV1,
+    engine_getBlobsV1,
+    engine_getBlobsV2,
+    // any other engine method, forwarded without looking at it
+    #[serde(untagged)]
+    Unknown(String),
+}
+
+impl EngineMethod {
+    // the method's name as it's sent over json-rpc
+    pub fn name(&self) -> String {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => name,
+            _ => format!("{:?}", self),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct RpcRequest {
     pub method: EngineMethod,
     pub params: serde_json::Value,
-    pub id: u64,
+    // any json value the caller picked, echoed back as is. missing means null
+    #[serde(default)]
+    pub id: serde_json::Value,
     pub jsonrpc: String,
 }
 
@@ -364,8 +460,46 @@ pub struct MetricsReport {
     pub response_times: HashMap<String, u128>,
     pub alive_nodes: Vec<String>,
     pub syncing_nodes: Vec<String>,
+    pub lagging_nodes: Vec<String>,
     pub dead_nodes: Vec<String>,
     pub primary_node: String,
+    pub node_states: HashMap<String, SyncingStatus>,
+    pub labels: HashMap<String, String>,
+    pub heads: HashMap<String, BlockHead>,
+    pub queue_depths: HashMap<String, usize>,
+    pub queue_drops: HashMap<String, u64>,
+    pub circuit_states: HashMap<String, circuit::CircuitState>,
+    pub blob_hits: HashMap<String, BlobHits>,
+    pub client_versions: HashMap<String, ClientVersionV1>,
+    // nodes running the same client on different versions
+    pub client_version_warnings: Vec<String>,
+}
+
+// one entry of an engine_getClientVersionV1 response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ClientVersionV1 {
+    // two letter client code, like GE for geth
+    pub code: String,
+    pub name: String,
+    pub version: String,
+    // first four bytes of the commit hash, 0x prefixed
+    pub commit: String,
+}
+
+// how many of the blobs asked for with getBlobs a node had
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct BlobHits {
+    pub requested: u64,
+    pub found: u64,
+    pub hit_rate: f64,
+}
+
+impl BlobHits {
+    pub fn record(&mut self, requested: usize, found: usize) {
+        self.requested += requested as u64;
+        self.found += found as u64;
+        self.hit_rate = self.found as f64 / self.requested.max(1) as f64;
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -373,6 +507,195 @@ pub struct NodeList {
     pub nodes: Vec<String>,
 }
 
+// picks a node by its url or label
+#[derive(Serialize, Deserialize)]
+pub struct NodeSelector {
+    pub node: String,
+}
+
+// what a node is used for besides voting
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeRole {
+    // picked as the primary node before any other synced node
+    Primary,
+    #[default]
+    Voter,
+    // only picked as the primary node if no other node is synced
+    Backup,
+}
+
+impl NodeRole {
+    // lower ranks are picked as the primary node first
+    pub fn rank(&self) -> u8 {
+        match self {
+            NodeRole::Primary => 0,
+            NodeRole::Voter => 1,
+            NodeRole::Backup => 2,
+        }
+    }
+}
+
+impl std::str::FromStr for NodeRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "primary" => Ok(NodeRole::Primary),
+            "voter" => Ok(NodeRole::Voter),
+            "backup" => Ok(NodeRole::Backup),
+            _ => Err(format!(
+                "Unknown node role {}, expected primary, voter or backup",
+                s
+            )),
+        }
+    }
+}
+
+// a node as it's written in the config file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct NodeConfig {
+    pub url: String,
+    // falls back to the general jwt secret
+    pub jwt_secret: Option<String>,
+    pub label: Option<String>,
+    // how much the node's response counts for in newPayload and fcU votes, 0 means it doesn't vote
+    pub weight: u32,
+    pub role: NodeRole,
+    // extra headers sent with every request to the node, like an api key for a proxy in front of it
+    pub headers: HashMap<String, String>,
+    // falls back to the general request timeout
+    pub timeout: Option<u64>,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        NodeConfig {
+            url: String::new(),
+            jwt_secret: None,
+            label: None,
+            weight: 1,
+            role: NodeRole::default(),
+            headers: HashMap::new(),
+            timeout: None,
+        }
+    }
+}
+
+impl NodeConfig {
+    // node urls can have options appended to them, like http://node:8551#jwt-secret=/path/to/secret#label=geth
+    // jwt secret paths can contain '#', anything after it that isn't another option is part of the path
+    pub fn from_spec(spec: &str) -> Result<Self, String> {
+        const KEYS: [&str; 5] = ["jwt-secret", "label", "weight", "role", "timeout"];
+
+        let mut options = spec.split('#');
+        let mut config = NodeConfig {
+            url: options.next().unwrap_or_default().trim().to_string(),
+            ..Default::default()
+        };
+
+        let mut last_key = "";
+        for option in options {
+            let (key, value) = match option.split_once('=') {
+                Some((key, value)) if KEYS.contains(&key) => (key, value),
+                _ if last_key == "jwt-secret" => {
+                    if let Some(jwt_secret) = config.jwt_secret.as_mut() {
+                        jwt_secret.push('#');
+                        jwt_secret.push_str(option);
+                    }
+                    continue;
+                }
+                Some((key, _)) => {
+                    return Err(format!("Unknown node option {} in {}", key, spec))
+                }
+                None => {
+                    return Err(format!(
+                        "Node option {} in {} is missing a value",
+                        option, spec
+                    ))
+                }
+            };
+            last_key = key;
+
+            match key {
+                "jwt-secret" => config.jwt_secret = Some(value.to_string()),
+                "label" => config.label = Some(value.to_string()),
+                "weight" => {
+                    config.weight = value
+                        .parse()
+                        .map_err(|e| format!("Invalid weight in {}: {}", spec, e))?
+                }
+                "role" => config.role = value.parse()?,
+                "timeout" => {
+                    config.timeout = Some(
+                        value
+                            .parse()
+                            .map_err(|e| format!("Invalid timeout in {}: {}", spec, e))?,
+                    )
+                }
+                _ => return Err(format!("Unknown node option {} in {}", key, spec)),
+            }
+        }
+
+        Ok(config)
+    }
+
+    // reads the node's own jwt secret, or falls back to the general one
+    pub fn jwt_key(
+        &self,
+        general_jwt: Option<&jsonwebtoken::EncodingKey>,
+    ) -> Result<jsonwebtoken::EncodingKey, String> {
+        match (&self.jwt_secret, general_jwt) {
+            (Some(jwt_path), _) => {
+                read_jwt(jwt_path).map_err(|e| format!("Could not encode jwt secret: {}", e))
+            }
+            (None, Some(general_jwt)) => Ok(general_jwt.clone()),
+            (None, None) => Err(format!(
+                "Node {} does not match specific or general jwt",
+                self.url
+            )),
+        }
+    }
+
+    pub fn create_node(
+        self,
+        general_jwt: Option<&jsonwebtoken::EncodingKey>,
+        default_timeout: Duration,
+    ) -> Result<Node, String> {
+        if self.url.is_empty() {
+            return Err("Node is missing a url".to_string());
+        }
+
+        let jwt_secret = self.jwt_key(general_jwt)?;
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in self.headers.iter() {
+            let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| format!("Invalid header name {} for {}: {}", name, self.url, e))?;
+            let value = reqwest::header::HeaderValue::from_str(value)
+                .map_err(|e| format!("Invalid header value for {} on {}: {}", name, self.url, e))?;
+            headers.insert(name, value);
+        }
+
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .map_err(|e| format!("Could not build http client for {}: {}", self.url, e))?;
+
+        let mut node = Node::with_client(client, self.url.clone(), jwt_secret);
+        node.label = self.label.clone();
+        node.weight = self.weight;
+        node.role = self.role;
+        node.timeout = self
+            .timeout
+            .map(Duration::from_millis)
+            .unwrap_or(default_timeout);
+        node.config = self;
+        Ok(node)
+    }
+}
+
 impl NodeList {
     pub fn from_nodes_vec(nodes_vec: &[Node]) -> Self {
         NodeList {
@@ -383,43 +706,121 @@ impl NodeList {
     pub fn create_new_nodes(
         self,
         general_jwt: Option<jsonwebtoken::EncodingKey>,
+        default_timeout: Duration,
     ) -> Result<Vec<Arc<Node>>, String> {
-        let re = match Regex::new(r"#jwt-secret=(.*)") {
-            Ok(re) => re,
+        let configs = self
+            .nodes
+            .iter()
+            .map(|node| NodeConfig::from_spec(node))
+            .collect::<Result<Vec<NodeConfig>, String>>()?;
+
+        create_nodes(configs, general_jwt.as_ref(), default_timeout)
+    }
+}
+
+pub fn create_nodes(
+    configs: Vec<NodeConfig>,
+    general_jwt: Option<&jsonwebtoken::EncodingKey>,
+    default_timeout: Duration,
+) -> Result<Vec<Arc<Node>>, String> {
+    let mut nodeinstances: Vec<Arc<Node>> = Vec::with_capacity(configs.len());
+
+    for config in configs {
+        match config.create_node(general_jwt, default_timeout) {
+            Ok(node) => nodeinstances.push(Arc::new(node)),
             Err(e) => {
-                tracing::error!("Failed to compile jwt matching secret: {}", e);
-                return Err(format!("Failed to compile jwt matching secret: {}", e));
+                tracing::error!("Could not create node: {}", e);
+                return Err(e);
             }
-        };
+        }
+    }
 
-        let mut nodeinstances: Vec<Arc<Node>> = Vec::with_capacity(self.nodes.len());
-
-        for node in self.nodes {
-            if let Some(captures) = re.captures(&node) {
-                if let Some(jwt_path) = captures.get(1) {
-                    let jwt_secret = match read_jwt(jwt_path.as_str()) {
-                        Ok(jwt_secret) => jwt_secret,
-                        Err(e) => {
-                            tracing::error!("Could not encode jwt secret: {}", e);
-                            return Err(format!("Could not encode jwt secret: {}", e));
-                        }
-                    };
-                    let node_str = re.replace(&node, "").to_string();
-                    let node = Arc::new(Node::new(node_str, jwt_secret));
-                    nodeinstances.push(node);
-                    continue;
-                }
-            } else if let Some(general_jwt) = &general_jwt {
-                nodeinstances.push(Arc::new(Node::new(node.to_string(), general_jwt.clone())))
-            } else {
-                tracing::error!("Node {} does not match specific or general jwt", node);
-                return Err(format!(
-                    "Node {} does nto match specific or general jwt",
-                    node
-                ));
-            }
+    Ok(nodeinstances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_config_from_spec() {
+        let config = NodeConfig::from_spec(
+            "http://node:8551#jwt-secret=/jwt#label=geth#weight=2#role=backup",
+        )
+        .unwrap();
+        assert_eq!(config.url, "http://node:8551");
+        assert_eq!(config.jwt_secret.as_deref(), Some("/jwt"));
+        assert_eq!(config.label.as_deref(), Some("geth"));
+        assert_eq!(config.weight, 2);
+        assert_eq!(config.role, NodeRole::Backup);
+
+        let config = NodeConfig::from_spec("http://node:8551").unwrap();
+        assert_eq!(config.weight, 1);
+        assert_eq!(config.role, NodeRole::Voter);
+
+        assert!(NodeConfig::from_spec("http://node:8551#colour=red").is_err());
+        assert!(NodeConfig::from_spec("http://node:8551#role=leader").is_err());
+        assert!(NodeConfig::from_spec("http://node:8551#label").is_err());
+
+        // '#' in a jwt secret path is kept, whether or not other options follow
+        let config = NodeConfig::from_spec("http://node:8551#jwt-secret=/secrets/#1/jwt").unwrap();
+        assert_eq!(config.jwt_secret.as_deref(), Some("/secrets/#1/jwt"));
+        let config =
+            NodeConfig::from_spec("http://node:8551#label=geth#jwt-secret=/a#b=c#d#weight=3")
+                .unwrap();
+        assert_eq!(config.jwt_secret.as_deref(), Some("/a#b=c#d"));
+        assert_eq!(config.label.as_deref(), Some("geth"));
+        assert_eq!(config.weight, 3);
+    }
+
+    #[test]
+    fn test_create_nodes_without_runtime() {
+        let config = NodeConfig::from_spec("http://node:8551").unwrap();
+        let jwt_key = jsonwebtoken::EncodingKey::from_secret(&[0; 32]);
+        let nodes = create_nodes(vec![config], Some(&jwt_key), Duration::from_secs(1)).unwrap();
+        assert_eq!(nodes[0].queue.depth(), 0);
+    }
+
+    #[test]
+    fn test_rpc_request_ids() {
+        for id in [
+            serde_json::json!(1),
+            serde_json::json!("abc"),
+            serde_json::Value::Null,
+        ] {
+            let request: RpcRequest = serde_json::from_value(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "engine_exchangeCapabilities",
+                "params": [[]]
+            }))
+            .unwrap();
+            assert_eq!(request.id, id);
         }
 
-        Ok(nodeinstances)
+        let request: RpcRequest = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","method":"engine_exchangeCapabilities","params":[[]]}"#,
+        )
+        .unwrap();
+        assert_eq!(request.id, serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_unknown_engine_method() {
+        let request: RpcRequest = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","id":1,"method":"engine_newPayloadV9","params":[]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            request.method,
+            EngineMethod::Unknown("engine_newPayloadV9".to_string())
+        );
+        // forwarded with the method name as it came in
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["method"],
+            "engine_newPayloadV9"
+        );
+        assert_eq!(request.method.name(), "engine_newPayloadV9");
+        assert_eq!(EngineMethod::engine_getBlobsV1.name(), "engine_getBlobsV1");
     }
 }
@@ -1,69 +1,423 @@
-use crate::*;
+use crate::{
+    circuit::CircuitBreaker,
+    state::{NodeTransition, TRANSITION_HISTORY_LEN},
+    *,
+};
 use jsonwebtoken;
 use reqwest;
 use serde_json::json;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::{mpsc, RwLock};
 use tracing;
 
+// a request waiting to be replicated to a node by its dispatch worker
+struct QueuedRequest {
+    body: Vec<u8>,
+    method: EngineMethod,
+}
+
+// bounded FIFO of requests replicated to a node in the background
+// each node has its own worker, so a slow node can't hold up the others or receive requests out of order
+#[derive(Clone)]
+pub struct DispatchQueue {
+    sender: mpsc::Sender<QueuedRequest>,
+    dropped: Arc<AtomicU64>,
+    // the worker is spawned on the first enqueue, so nodes can be made outside a tokio runtime
+    worker: Arc<std::sync::Mutex<Option<PendingWorker>>>,
+}
+
+// what the dispatch worker is started with
+struct PendingWorker {
+    client: reqwest::Client,
+    url: String,
+    jwt_key: SharedJwtKey,
+    receiver: mpsc::Receiver<QueuedRequest>,
+}
+
+impl DispatchQueue {
+    fn new(client: reqwest::Client, url: String, jwt_key: SharedJwtKey) -> Self {
+        let (sender, receiver) = mpsc::channel(DISPATCH_QUEUE_SIZE);
+        DispatchQueue {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+            worker: Arc::new(std::sync::Mutex::new(Some(PendingWorker {
+                client,
+                url,
+                jwt_key,
+                receiver,
+            }))),
+        }
+    }
+
+    fn start_worker(&self) {
+        if let Some(worker) = self.worker.lock().unwrap().take() {
+            tokio::spawn(dispatch_worker(
+                worker.client,
+                worker.url,
+                worker.jwt_key,
+                worker.receiver,
+            ));
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+// sends queued requests one at a time, in the order they were queued
+// exits once the node (and with it the sender) is dropped
+async fn dispatch_worker(
+    client: reqwest::Client,
+    url: String,
+    jwt_key: SharedJwtKey,
+    mut receiver: mpsc::Receiver<QueuedRequest>,
+) {
+    while let Some(queued) = receiver.recv().await {
+        // sign when sending, a token made when the request was queued could be stale by now
+        let token = match make_jwt(&jwt_key.read().unwrap()) {
+            Ok(token) => token,
+            Err(e) => {
+                tracing::error!("Could not make jwt for queued request to {}: {}", url, e);
+                continue;
+            }
+        };
+
+        let resp = client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", token))
+            .body(queued.body)
+            .timeout(*DISPATCH_TIMEOUT)
+            .send()
+            .await;
+
+        if let Err(e) = resp {
+            tracing::warn!(
+                "Error while sending queued {:?} to node {}: {}",
+                queued.method,
+                url,
+                e
+            );
+        }
+    }
+}
+
+// the node's jwt secret, shared with its dispatch worker so the key can be rotated while it runs
+type SharedJwtKey = Arc<std::sync::RwLock<jsonwebtoken::EncodingKey>>;
+
 #[derive(Clone)]
 pub struct Node
 // represents an EE
 {
     pub client: reqwest::Client,
     pub url: String,
+    pub label: Option<String>,
+    pub weight: u32,
+    pub role: NodeRole,
+    pub timeout: Duration,
     pub status: Arc<RwLock<NodeHealth>>,
-    pub jwt_key: jsonwebtoken::EncodingKey,
+    jwt_key: SharedJwtKey,
+    // what the node was created from, to tell if it changed when the config is reloaded
+    pub config: NodeConfig,
+    pub queue: DispatchQueue,
+    pub circuit: Arc<CircuitBreaker>,
+    pub transitions: Arc<RwLock<VecDeque<NodeTransition>>>,
+    // what the node answered to the last engine_getClientVersionV1 EB sent it
+    pub client_version: Arc<RwLock<Option<ClientVersionV1>>>,
+    // set once the node's chain id and genesis hash matched ours
+    pub chain_verified: Arc<AtomicBool>,
 }
 
 impl Node {
     pub fn new(url: String, jwt_key: jsonwebtoken::EncodingKey) -> Node {
-        let client = reqwest::Client::new();
+        Node::with_client(reqwest::Client::new(), url, jwt_key)
+    }
+
+    pub fn with_client(
+        client: reqwest::Client,
+        url: String,
+        jwt_key: jsonwebtoken::EncodingKey,
+    ) -> Node {
+        let jwt_key = Arc::new(std::sync::RwLock::new(jwt_key));
+        let queue = DispatchQueue::new(client.clone(), url.clone(), jwt_key.clone());
         Node {
             client,
+            config: NodeConfig {
+                url: url.clone(),
+                ..Default::default()
+            },
             url,
+            label: None,
+            weight: 1,
+            role: NodeRole::default(),
+            timeout: *TIMEOUT,
             status: Arc::new(RwLock::new(NodeHealth {
                 status: SyncingStatus::NodeNotInitialized,
                 resp_time: 0,
+                head: None,
             })),
             jwt_key,
+            queue,
+            circuit: Arc::new(CircuitBreaker::default()),
+            transitions: Arc::new(RwLock::new(VecDeque::with_capacity(TRANSITION_HISTORY_LEN))),
+            client_version: Arc::new(RwLock::new(None)),
+            chain_verified: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // a fresh token signed with the node's jwt secret
+    pub fn make_jwt(&self) -> Result<String, jsonwebtoken::errors::Error> {
+        make_jwt(&self.jwt_key.read().unwrap())
+    }
+
+    // also used by requests already queued for the node
+    pub fn set_jwt_key(&self, jwt_key: jsonwebtoken::EncodingKey) {
+        *self.jwt_key.write().unwrap() = jwt_key;
+    }
+
+    // queue a request to be replicated to this node without waiting for it
+    // if the queue is full the request is dropped, since the node is too far behind to keep up anyway
+    // must be called from within a tokio runtime, the first call starts the node's dispatch worker
+    pub fn enqueue(&self, request: &RpcRequest) {
+        self.queue.start_worker();
+        let queued = QueuedRequest {
+            body: request.as_bytes(),
+            method: request.method.clone(),
+        };
+
+        match self.queue.sender.try_send(queued) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(queued)) => {
+                self.queue.dropped.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(
+                    "Dispatch queue for {} is full, dropping {:?}",
+                    self.url,
+                    queued.method
+                );
+            }
+            Err(mpsc::error::TrySendError::Closed(queued)) => {
+                tracing::error!(
+                    "Dispatch worker for {} has stopped, dropping {:?}",
+                    self.url,
+                    queued.method
+                );
+            }
+        }
+    }
+
+    // whether `name` is this node's url or label
+    pub fn matches(&self, name: &str) -> bool {
+        self.url == name || self.label.as_deref() == Some(name)
+    }
+
+    // moves the node to a new state, recording why
+    // returns false if it was already in that state
+    pub async fn transition(&self, to: SyncingStatus, reason: &str) -> bool {
+        let mut status = self.status.write().await;
+        let from = status.status;
+        if from == to {
+            return false;
+        }
+        status.status = to;
+        drop(status);
+
+        match to {
+            SyncingStatus::Synced | SyncingStatus::OnlineAndSyncing => {
+                tracing::info!(
+                    "Node {} is now {:?} (was {:?}): {}",
+                    self.url,
+                    to,
+                    from,
+                    reason
+                )
+            }
+            _ => tracing::warn!(
+                "Node {} is now {:?} (was {:?}): {}",
+                self.url,
+                to,
+                from,
+                reason
+            ),
         }
+
+        let mut transitions = self.transitions.write().await;
+        if transitions.len() == TRANSITION_HISTORY_LEN {
+            transitions.pop_front();
+        }
+        transitions.push_back(NodeTransition {
+            node: self.url.clone(),
+            from,
+            to,
+            reason: reason.to_string(),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        });
+
+        true
     }
 
-    pub async fn set_synced(&self) {
-        let status = self.status.read().await;
-        if status.status != SyncingStatus::Synced {
-            tracing::info!("Node {} is synced", self.url);
-            drop(status);
-            let mut status = self.status.write().await;
-            status.status = SyncingStatus::Synced;
+    // gets the number and hash of the node's latest block
+    async fn get_head(&self, token: &str) -> Result<Option<BlockHead>, reqwest::Error> {
+        let json_body: serde_json::Value = self
+            .client
+            .post(self.url.clone())
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/json")
+            .json(&json!({"jsonrpc": "2.0", "method": "eth_getBlockByNumber", "params": ["latest", false], "id": 1}))
+            .timeout(self.timeout)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        match serde_json::from_value::<BlockHead>(json_body["result"].clone()) {
+            Ok(head) => Ok(Some(head)),
+            Err(e) => {
+                tracing::warn!("Could not get latest block from {}: {}", self.url, e);
+                Ok(None)
+            }
         }
-        // it's already set as synced
     }
 
-    pub async fn set_offline(&self) {
-        let status = self.status.read().await;
-        if status.status != SyncingStatus::Offline {
-            tracing::warn!("Node {} is offline", self.url);
-            drop(status);
-            let mut status = self.status.write().await;
-            status.status = SyncingStatus::Offline;
+    // asks the node which client it's running and remembers it, None if it doesn't support engine_getClientVersionV1
+    pub async fn get_client_version(
+        &self,
+        own_version: &ClientVersionV1,
+    ) -> Result<Option<ClientVersionV1>, reqwest::Error> {
+        let token = self.make_jwt().unwrap();
+        let json_body: serde_json::Value = self
+            .client
+            .post(self.url.clone())
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/json")
+            .json(&json!({"jsonrpc": "2.0", "method": "engine_getClientVersionV1", "params": [own_version], "id": 1}))
+            .timeout(self.timeout)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        // ELs answer with a single entry, only the first is kept if there are more
+        let version = serde_json::from_value::<Vec<ClientVersionV1>>(json_body["result"].clone())
+            .ok()
+            .and_then(|versions| versions.into_iter().next());
+        *self.client_version.write().await = version.clone();
+        Ok(version)
+    }
+
+    // asks the node which engine methods it supports, None if it doesn't support engine_exchangeCapabilities
+    pub async fn exchange_capabilities(
+        &self,
+        own_capabilities: &[&str],
+    ) -> Result<Option<Vec<String>>, reqwest::Error> {
+        let token = self.make_jwt().unwrap();
+        let json_body: serde_json::Value = self
+            .client
+            .post(self.url.clone())
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/json")
+            .json(&json!({"jsonrpc": "2.0", "method": "engine_exchangeCapabilities", "params": [own_capabilities], "id": 1}))
+            .timeout(self.timeout)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        Ok(serde_json::from_value::<Vec<String>>(json_body["result"].clone()).ok())
+    }
+
+    async fn get_result(
+        &self,
+        token: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, ChainCheckError> {
+        let resp = self
+            .client
+            .post(self.url.clone())
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/json")
+            .json(&json!({"jsonrpc": "2.0", "method": method, "params": params, "id": 1}))
+            .timeout(self.timeout)
+            .send()
+            .await
+            .map_err(|e| ChainCheckError::Unverified(e.to_string()))?;
+        if !resp.status().is_success() {
+            return Err(ChainCheckError::Unverified(format!(
+                "{} returned status {}",
+                method,
+                resp.status()
+            )));
+        }
+
+        let mut json_body: serde_json::Value = resp
+            .json()
+            .await
+            .map_err(|e| ChainCheckError::Unverified(e.to_string()))?;
+        match json_body["result"].take() {
+            serde_json::Value::Null => Err(ChainCheckError::Unverified(format!(
+                "no result for {}",
+                method
+            ))),
+            result => Ok(result),
         }
     }
 
-    pub async fn set_online_and_syncing(&self) {
-        let status = self.status.read().await;
-        if status.status != SyncingStatus::OnlineAndSyncing {
-            tracing::info!("Node {} is online and syncing", self.url);
-            drop(status);
-            let mut status = self.status.write().await;
-            status.status = SyncingStatus::OnlineAndSyncing;
+    // checks the node's chain id and genesis hash against ours, once they've matched the node isn't asked again
+    pub async fn verify_chain(
+        &self,
+        chain_id: u64,
+        genesis_hash: H256,
+    ) -> Result<(), ChainCheckError> {
+        if self.chain_verified.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let token = self
+            .make_jwt()
+            .map_err(|e| ChainCheckError::Unverified(e.to_string()))?;
+
+        let node_chain_id = self.get_result(&token, "eth_chainId", json!([])).await?;
+        let node_chain_id = serde_json::from_value::<QuantityU64>(node_chain_id)
+            .map_err(|e| ChainCheckError::Unverified(format!("invalid chain id: {}", e)))?
+            .value;
+        if node_chain_id != chain_id {
+            return Err(ChainCheckError::WrongChain(format!(
+                "{} is on chain {}, expected {}",
+                self.url, node_chain_id, chain_id
+            )));
         }
+
+        let genesis = self
+            .get_result(&token, "eth_getBlockByNumber", json!(["0x0", false]))
+            .await?;
+        let genesis = serde_json::from_value::<BlockHead>(genesis)
+            .map_err(|e| ChainCheckError::Unverified(format!("invalid genesis block: {}", e)))?;
+        if genesis.hash != genesis_hash {
+            return Err(ChainCheckError::WrongChain(format!(
+                "{} has genesis hash {:?}, expected {:?}",
+                self.url, genesis.hash, genesis_hash
+            )));
+        }
+
+        self.chain_verified.store(true, Ordering::Relaxed);
+        Ok(())
     }
 
+    // asks the node whether it's synced and updates its response time and head
+    // the status of the returned health is what the node reported, the router decides what state that puts the node in
     pub async fn check_status(&self) -> Result<NodeHealth, reqwest::Error> {
         // we need to use jwt here since we're talking directly to the EE's auth port
-        let token = make_jwt(&self.jwt_key).unwrap();
+        let token = self.make_jwt().unwrap();
         let start = std::time::Instant::now();
         let resp = self
             .client
@@ -71,40 +425,42 @@ impl Node {
             .header("Authorization", format!("Bearer {}", token))
             .header("Content-Type", "application/json")
             .json(&json!({"jsonrpc": "2.0", "method": "eth_syncing", "params": [], "id": 1}))
-            .timeout(*TIMEOUT)
+            .timeout(self.timeout)
             .send()
-            .await;
+            .await?;
         let resp_time = start.elapsed().as_micros();
-        let resp: reqwest::Response = match resp {
-            Ok(resp) => resp,
-            Err(e) => {
-                self.set_offline().await;
-                return Err(e);
-            }
-        };
 
-        // deserialize the json response.
-        // result = false means node is online and not syncing
-        // result = an object means node is syncing
-        let json_body: serde_json::Value = resp.json().await?;
-        let result = &json_body["result"];
+        let mut head = None;
+        let observed = if resp.status() == reqwest::StatusCode::UNAUTHORIZED
+            || resp.status() == reqwest::StatusCode::FORBIDDEN
+        {
+            SyncingStatus::AuthFailed
+        } else {
+            // deserialize the json response.
+            // result = false means node is online and not syncing
+            // result = an object means node is syncing
+            let json_body: serde_json::Value = resp.json().await?;
+            let result = &json_body["result"];
 
-        if result.is_boolean() {
-            if !result.as_bool().unwrap() {
+            if result.is_boolean() && !result.as_bool().unwrap() {
                 // unwrap is safe due to check above
-                self.set_synced().await;
+                head = self.get_head(&token).await?;
+                SyncingStatus::Synced
             } else {
-                self.set_online_and_syncing().await;
+                // syncing nodes return a object reporting the sync status
+                SyncingStatus::OnlineAndSyncing
             }
-        } else {
-            // syncing nodes return a object reporting the sync status
-            self.set_online_and_syncing().await;
-        }
+        };
 
         // update the status
         let mut status = self.status.write().await;
         status.resp_time = resp_time;
-        Ok(status.clone())
+        status.head = head;
+        Ok(NodeHealth {
+            status: observed,
+            resp_time,
+            head,
+        })
     }
 
     pub async fn do_request(
@@ -118,7 +474,7 @@ impl Node {
             .header("Content-Type", "application/json")
             .header("Authorization", jwt_token)
             .body(data.as_bytes())
-            .timeout(*TIMEOUT)
+            .timeout(self.timeout)
             .send()
             .await;
 
@@ -0,0 +1,82 @@
+use crate::SyncingStatus;
+use serde::{Deserialize, Serialize};
+
+// how many transitions each node keeps for the events api
+pub const TRANSITION_HISTORY_LEN: usize = 64;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeTransition {
+    pub node: String,
+    pub from: SyncingStatus,
+    pub to: SyncingStatus,
+    pub reason: String,
+    // unix time in milliseconds
+    pub timestamp: i64,
+}
+
+// decides which state a node should be in after a health check
+// `observed` is what the health check saw (Synced, OnlineAndSyncing, Offline, AuthFailed, WrongChain or ChainUnverified)
+pub fn next_state(
+    current: SyncingStatus,
+    observed: SyncingStatus,
+    lagging: bool,
+    circuit_open: bool,
+) -> SyncingStatus {
+    // only an admin takes a node out of maintenance
+    if current == SyncingStatus::Maintenance {
+        return SyncingStatus::Maintenance;
+    }
+
+    // failing engine calls matter more than the health check, the node stays out until its circuit can be probed
+    if circuit_open {
+        return SyncingStatus::Quarantined;
+    }
+
+    match observed {
+        SyncingStatus::Synced if lagging => SyncingStatus::Lagging,
+        SyncingStatus::Synced => SyncingStatus::Synced,
+        SyncingStatus::OnlineAndSyncing => SyncingStatus::OnlineAndSyncing,
+        SyncingStatus::AuthFailed => SyncingStatus::AuthFailed,
+        SyncingStatus::WrongChain => SyncingStatus::WrongChain,
+        SyncingStatus::ChainUnverified => SyncingStatus::ChainUnverified,
+        _ => SyncingStatus::Offline,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_state() {
+        use SyncingStatus::*;
+
+        assert_eq!(next_state(NodeNotInitialized, Synced, false, false), Synced);
+        assert_eq!(next_state(Synced, Synced, true, false), Lagging);
+        assert_eq!(next_state(Lagging, Synced, false, false), Synced);
+        assert_eq!(next_state(Synced, Offline, false, false), Offline);
+        assert_eq!(next_state(Synced, AuthFailed, false, false), AuthFailed);
+        assert_eq!(
+            next_state(NodeNotInitialized, WrongChain, false, false),
+            WrongChain
+        );
+        // a node that hasn't shown its chain isn't let in, however synced it says it is
+        assert_eq!(
+            next_state(NodeNotInitialized, ChainUnverified, false, false),
+            ChainUnverified
+        );
+        assert_eq!(next_state(ChainUnverified, Synced, false, false), Synced);
+
+        // quarantine holds until the circuit can be probed, then the health check decides
+        assert_eq!(next_state(Synced, Synced, false, true), Quarantined);
+        assert_eq!(next_state(Quarantined, Synced, false, true), Quarantined);
+        assert_eq!(
+            next_state(Quarantined, OnlineAndSyncing, false, false),
+            OnlineAndSyncing
+        );
+
+        // maintenance is only left through the admin api
+        assert_eq!(next_state(Maintenance, Synced, false, false), Maintenance);
+        assert_eq!(next_state(Maintenance, Offline, false, true), Maintenance);
+    }
+}